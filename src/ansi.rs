@@ -0,0 +1,257 @@
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+
+/// Turns a line of process output into styled spans, applying SGR colour
+/// and style sequences and dropping every other escape sequence
+pub fn parse_line(line: &str) -> Line<'static> {
+    let mut spans = vec![];
+    let mut style = Style::default();
+    let mut text = String::new();
+
+    for token in tokenize(line) {
+        match token {
+            Token::Text(t) => text.push_str(t),
+            Token::Sgr(params) => {
+                if !text.is_empty() {
+                    spans.push(Span::styled(std::mem::take(&mut text), style));
+                }
+                style = apply_sgr(style, params);
+            }
+            Token::Other => {}
+        }
+    }
+
+    if !text.is_empty() {
+        spans.push(Span::styled(text, style));
+    }
+
+    Line::from(spans)
+}
+
+/// The line without any escape sequences
+pub fn strip(line: &str) -> String {
+    tokenize(line)
+        .filter_map(|token| match token {
+            Token::Text(t) => Some(t),
+            _ => None,
+        })
+        .collect()
+}
+
+enum Token<'a> {
+    Text(&'a str),
+    /// Parameters of `ESC [ ... m`
+    Sgr(&'a str),
+    /// Any other escape sequence, e.g. cursor movement or window titles
+    Other,
+}
+
+fn tokenize(line: &str) -> impl Iterator<Item = Token<'_>> {
+    let mut rest = line;
+
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+
+        let Some(esc) = rest.find('\x1b') else {
+            let text = rest;
+            rest = "";
+            return Some(Token::Text(text));
+        };
+
+        if esc > 0 {
+            let text = &rest[..esc];
+            rest = &rest[esc..];
+            return Some(Token::Text(text));
+        }
+
+        let bytes = rest.as_bytes();
+        let (token, len) = match bytes.get(1) {
+            // CSI: parameters and intermediates, ended by a byte in @..~
+            Some(b'[') => {
+                let end = bytes[2..]
+                    .iter()
+                    .position(|b| (0x40..=0x7e).contains(b))
+                    .map(|i| i + 2);
+
+                match end {
+                    Some(end) if bytes[end] == b'm' => (Token::Sgr(&rest[2..end]), end + 1),
+                    Some(end) => (Token::Other, end + 1),
+                    None => (Token::Other, rest.len()),
+                }
+            }
+            // OSC: ended by whichever of BEL or ESC \ comes first
+            Some(b']') => {
+                let bel = rest.find('\x07').map(|i| i + 1);
+                let st = rest.find("\x1b\\").map(|i| i + 2);
+                let end = match (bel, st) {
+                    (Some(bel), Some(st)) => bel.min(st),
+                    (bel, st) => bel.or(st).unwrap_or(rest.len()),
+                };
+                (Token::Other, end)
+            }
+            // ESC, any intermediate bytes like the ( in ESC ( B, then a
+            // final character
+            Some(_) => {
+                let intermediates = bytes[1..]
+                    .iter()
+                    .take_while(|b| (0x20..=0x2f).contains(*b))
+                    .count();
+                let start = 1 + intermediates;
+                let last = rest[start..].chars().next().map_or(0, |c| c.len_utf8());
+                (Token::Other, start + last)
+            }
+            // A lone ESC at the end
+            None => (Token::Other, 1),
+        };
+
+        rest = &rest[len..];
+        Some(token)
+    })
+}
+
+fn apply_sgr(mut style: Style, params: &str) -> Style {
+    // `38:5:n` is an alternative spelling of `38;5;n`
+    let mut codes = params
+        .split([';', ':'])
+        .map(|x| if x.is_empty() { 0 } else { x.parse::<u16>().unwrap_or(u16::MAX) });
+
+    while let Some(code) = codes.next() {
+        style = match code {
+            0 => Style::default(),
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            5 | 6 => style.add_modifier(Modifier::SLOW_BLINK),
+            7 => style.add_modifier(Modifier::REVERSED),
+            8 => style.add_modifier(Modifier::HIDDEN),
+            9 => style.add_modifier(Modifier::CROSSED_OUT),
+            22 => style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+            23 => style.remove_modifier(Modifier::ITALIC),
+            24 => style.remove_modifier(Modifier::UNDERLINED),
+            25 => style.remove_modifier(Modifier::SLOW_BLINK),
+            27 => style.remove_modifier(Modifier::REVERSED),
+            28 => style.remove_modifier(Modifier::HIDDEN),
+            29 => style.remove_modifier(Modifier::CROSSED_OUT),
+            30..=37 => style.fg(basic_color(code - 30)),
+            38 => match extended_color(&mut codes) {
+                Some(color) => style.fg(color),
+                None => style,
+            },
+            39 => Style { fg: None, ..style },
+            40..=47 => style.bg(basic_color(code - 40)),
+            48 => match extended_color(&mut codes) {
+                Some(color) => style.bg(color),
+                None => style,
+            },
+            49 => Style { bg: None, ..style },
+            90..=97 => style.fg(bright_color(code - 90)),
+            100..=107 => style.bg(bright_color(code - 100)),
+            _ => style,
+        };
+    }
+
+    style
+}
+
+/// The colour after a 38 or 48: `5;n` for the 256 colour palette or
+/// `2;r;g;b` for true colour
+fn extended_color(codes: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    let byte = |x: u16| u8::try_from(x).ok();
+
+    match codes.next()? {
+        5 => Some(Color::Indexed(byte(codes.next()?)?)),
+        2 => Some(Color::Rgb(
+            byte(codes.next()?)?,
+            byte(codes.next()?)?,
+            byte(codes.next()?)?,
+        )),
+        _ => None,
+    }
+}
+
+fn basic_color(n: u16) -> Color {
+    match n {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        _ => Color::Gray,
+    }
+}
+
+fn bright_color(n: u16) -> Color {
+    match n {
+        0 => Color::DarkGray,
+        1 => Color::LightRed,
+        2 => Color::LightGreen,
+        3 => Color::LightYellow,
+        4 => Color::LightBlue,
+        5 => Color::LightMagenta,
+        6 => Color::LightCyan,
+        _ => Color::White,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_sgr_and_cursor_sequences() {
+        assert_eq!(strip("\x1b[1;31merror\x1b[0m: failed"), "error: failed");
+        assert_eq!(strip("\x1b[2K\x1b[1Gdone"), "done");
+        assert_eq!(strip("\x1b(Bplain"), "plain");
+    }
+
+    #[test]
+    fn osc_ended_by_bel() {
+        assert_eq!(strip("\x1b]0;title\x07text"), "text");
+    }
+
+    #[test]
+    fn osc_ended_by_st() {
+        assert_eq!(strip("\x1b]0;title\x1b\\text"), "text");
+    }
+
+    #[test]
+    fn osc_ends_at_the_first_terminator() {
+        assert_eq!(strip("\x1b]0;a\x07one\x1b]0;b\x1b\\two"), "onetwo");
+        assert_eq!(strip("\x1b]0;a\x1b\\one\x1b]0;b\x07two"), "onetwo");
+        // A hyperlink is two back-to-back OSCs around the text
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\ after";
+        assert_eq!(strip(link), "link after");
+    }
+
+    #[test]
+    fn unterminated_osc_drops_the_rest() {
+        assert_eq!(strip("before\x1b]0;title"), "before");
+        assert_eq!(strip("before\x1b[31"), "before");
+        assert_eq!(strip("before\x1b("), "before");
+        assert_eq!(strip("before\x1b"), "before");
+    }
+
+    #[test]
+    fn applies_colours() {
+        let line = parse_line("a\x1b[31mb\x1b[1;38;5;42mc\x1b[39;48;2;1;2;3md\x1b[0me");
+        let styles: Vec<_> = line.spans.iter().map(|x| (x.content.as_ref(), x.style)).collect();
+
+        assert_eq!(
+            styles,
+            [
+                ("a", Style::default()),
+                ("b", Style::default().fg(Color::Red)),
+                ("c", Style::default().fg(Color::Indexed(42)).add_modifier(Modifier::BOLD)),
+                ("d", Style::default().bg(Color::Rgb(1, 2, 3)).add_modifier(Modifier::BOLD)),
+                ("e", Style::default()),
+            ]
+        );
+    }
+}
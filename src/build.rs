@@ -0,0 +1,72 @@
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::util::Fnv;
+
+const CACHE_DIR: &str = ".dawn/builds";
+
+/// Hashes the files matched by the input globs, relative to `cwd`
+pub fn hash_inputs(cwd: &Path, patterns: &[String]) -> Result<u64, String> {
+    let mut paths: Vec<PathBuf> = vec![];
+
+    for pattern in patterns {
+        // `glob` matches nothing for a trailing `**`, so `src/**` would
+        // otherwise silently hash no files
+        let full = match pattern.strip_suffix("**") {
+            Some(prefix) => cwd.join(format!("{prefix}**/*")),
+            None => cwd.join(pattern),
+        };
+        let entries = glob::glob(&full.to_string_lossy())
+            .map_err(|e| format!("Invalid build input {pattern}: {e}"))?;
+
+        for entry in entries.flatten() {
+            collect_files(&entry, &mut paths);
+        }
+    }
+
+    paths.sort();
+    paths.dedup();
+
+    let mut hash = Fnv::new();
+    for path in paths {
+        let content = fs::read(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+        hash.write(path.to_string_lossy().as_bytes());
+        hash.write(&[0]);
+        hash.write(&content);
+    }
+
+    Ok(hash.finish())
+}
+
+// Directories given as inputs (e.g. `src`) count as all of their files
+fn collect_files(path: &Path, out: &mut Vec<PathBuf>) {
+    if path.is_file() {
+        out.push(path.to_path_buf());
+    } else if let Ok(entries) = fs::read_dir(path) {
+        for entry in entries.flatten() {
+            collect_files(&entry.path(), out);
+        }
+    }
+}
+
+pub fn cached_hash(name: &str) -> Option<u64> {
+    let content = fs::read_to_string(cache_path(name)).ok()?;
+    u64::from_str_radix(content.trim(), 16).ok()
+}
+
+pub fn store_hash(name: &str, hash: u64) -> io::Result<()> {
+    fs::create_dir_all(CACHE_DIR)?;
+    fs::write(cache_path(name), format!("{hash:016x}\n"))
+}
+
+fn cache_path(name: &str) -> PathBuf {
+    let file: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+
+    Path::new(CACHE_DIR).join(file)
+}
@@ -0,0 +1,59 @@
+use std::env;
+
+#[derive(Default)]
+pub struct Args {
+    /// Processes to start even if they have `autostart = false`
+    pub start: Vec<String>,
+    /// Processes to leave idle even if they have `autostart = true`
+    pub skip: Vec<String>,
+    pub profile: Option<String>,
+    /// Print process output to stdout instead of showing the TUI
+    pub no_tui: bool,
+}
+
+impl Args {
+    pub fn should_start(&self, name: &str, autostart: bool) -> bool {
+        if self.skip.iter().any(|x| x == name) {
+            return false;
+        }
+
+        autostart || self.start.iter().any(|x| x == name)
+    }
+}
+
+pub fn parse_args() -> Result<Args, String> {
+    let mut args = Args::default();
+    let mut iter = env::args().skip(1);
+
+    while let Some(arg) = iter.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg.clone(), None),
+        };
+
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| iter.next())
+                .ok_or_else(|| format!("Missing value for {flag}"))
+        };
+
+        match flag.as_str() {
+            "--start" => args.start.extend(split_names(&value()?)),
+            "--skip" => args.skip.extend(split_names(&value()?)),
+            "--profile" => args.profile = Some(value()?),
+            "--no-tui" if inline_value.is_none() => args.no_tui = true,
+            _ => return Err(format!("Unknown argument: {arg}")),
+        }
+    }
+
+    Ok(args)
+}
+
+fn split_names(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .collect()
+}
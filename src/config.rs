@@ -1,18 +1,377 @@
 use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 
+use crate::keybinds::KeyList;
+
 #[derive(Deserialize)]
 pub struct Config {
     pub processes: Vec<ProcessConfig>,
+    /// How many processes "Update All" updates at once
+    #[serde(default = "default_update_concurrency")]
+    pub update_concurrency: usize,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Replaces the default keys of an action, e.g. `stop = ["ctrl+x", "X"]`
+    #[serde(default)]
+    pub keybinds: HashMap<String, KeyList>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone, PartialEq)]
 pub struct ProcessConfig {
     pub name: String,
     pub cmd: Vec<String>,
     pub cwd: Option<String>,
     pub port: Option<u16>,
-    pub update_cmd: Option<Vec<String>>
+    pub update_cmd: Option<UpdateCmd>,
+    /// Keep the process running while updating and only restart it once the
+    /// update (and build) succeeded
+    #[serde(default)]
+    pub update_keep_running: bool,
+    pub update_if: Option<UpdateIf>,
+    pub group: Option<String>,
+    #[serde(rename = "type", default)]
+    pub kind: ProcessKind,
+    /// Processes that must be running (services) or have exited successfully
+    /// (tasks) before this one starts
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Cron expression for running a task periodically
+    pub schedule: Option<String>,
+    /// What to do when a scheduled run is due while the previous one is still going
+    #[serde(default)]
+    pub overlap: Overlap,
+    #[serde(default = "default_autostart")]
+    pub autostart: bool,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub instances: Option<u32>,
+    #[serde(flatten)]
+    pub hooks: Hooks,
+    pub build_cmd: Option<Vec<String>>,
+    /// Globs relative to `cwd`; the build is skipped while they are unchanged
+    #[serde(default)]
+    pub build_inputs: Vec<String>,
+    /// Globs relative to `cwd` that restart the process when changed
+    #[serde(default)]
+    pub watch: Vec<String>,
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    pub debounce_ms: Option<u64>,
+    /// Resident memory of the whole process tree, e.g. "512MiB" or "2G"
+    pub max_memory: Option<String>,
+    pub max_cpu_percent: Option<f64>,
+    /// How long usage has to stay above a limit before acting on it
+    pub limit_window_secs: Option<u64>,
+    #[serde(default)]
+    pub on_limit: LimitAction,
+    /// Sets FORCE_COLOR and CLICOLOR_FORCE, as output goes to a pipe rather
+    /// than a terminal
+    #[serde(default)]
+    pub force_color: bool,
+    /// Shows logs without colours instead of rendering escape sequences
+    #[serde(default)]
+    pub strip_ansi: bool,
+    /// Runs the process on a pseudo-terminal instead of pipes, for tools that
+    /// behave differently when not on a terminal
+    #[serde(default)]
+    pub pty: bool,
+}
+
+/// Either a single command or a list of named steps
+#[derive(Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum UpdateCmd {
+    Single(Vec<String>),
+    Steps(Vec<UpdateStep>),
+}
+
+#[derive(Deserialize, Clone, PartialEq)]
+pub struct UpdateStep {
+    pub name: Option<String>,
+    pub cmd: Vec<String>,
+    /// Relative to the process's `cwd`
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+impl UpdateStep {
+    pub fn display_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.cmd.join(" "))
+    }
+}
+
+impl UpdateCmd {
+    pub fn steps(&self) -> Vec<UpdateStep> {
+        match self {
+            UpdateCmd::Single(cmd) => vec![UpdateStep {
+                name: None,
+                cmd: cmd.clone(),
+                cwd: None,
+                continue_on_error: false,
+            }],
+            UpdateCmd::Steps(steps) => steps.clone(),
+        }
+    }
+}
+
+/// Commands run around starting and stopping the process
+#[derive(Deserialize, Clone, PartialEq, Default)]
+pub struct Hooks {
+    /// Blocks the start if it fails
+    pub pre_start: Option<Vec<String>>,
+    pub post_start: Option<Vec<String>>,
+    pub pre_stop: Option<Vec<String>>,
+    pub post_stop: Option<Vec<String>>,
+    /// How long pre_stop and post_stop may run before they're killed
+    pub stop_hook_timeout_secs: Option<u64>,
+}
+
+/// Parses sizes like `2GiB`, `512MB`, `100M` or `4096`. Binary units are
+/// 1024 based, and so are bare `K`, `M` and `G`.
+pub fn parse_bytes(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size: {value}"))?;
+
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        _ => return Err(format!("Invalid size unit in {value}")),
+    };
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+fn default_autostart() -> bool {
+    true
+}
+
+fn default_update_concurrency() -> usize {
+    4
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessKind {
+    /// Expected to keep running
+    #[default]
+    Service,
+    /// Expected to run to completion, e.g. migrations or codegen
+    Task,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitAction {
+    #[default]
+    Restart,
+    /// Only log and show a warning
+    Warn,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Overlap {
+    #[default]
+    Skip,
+    /// Run again as soon as the previous run finished
+    Queue,
+    /// Stop the previous run and start a new one
+    Kill,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateIf {
+    /// Only update when the upstream branch has new commits
+    Git,
+}
+
+#[derive(Deserialize, Default)]
+pub struct Profile {
+    /// Processes to run in this profile, all of them when omitted
+    pub processes: Option<Vec<String>>,
+    #[serde(default)]
+    pub overrides: HashMap<String, ProcessOverride>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct ProcessOverride {
+    pub cmd: Option<Vec<String>>,
+    /// Extra arguments appended to `cmd`
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    pub port: Option<u16>,
+    pub autostart: Option<bool>,
+    /// Merged over the process's own `env`
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+}
+
+impl ProcessOverride {
+    fn apply(&self, config: &mut ProcessConfig) {
+        if let Some(ref cmd) = self.cmd {
+            config.cmd = cmd.clone();
+        }
+        config.cmd.extend(self.args.iter().cloned());
+
+        if self.cwd.is_some() {
+            config.cwd = self.cwd.clone();
+        }
+        if self.port.is_some() {
+            config.port = self.port;
+        }
+        if let Some(autostart) = self.autostart {
+            config.autostart = autostart;
+        }
+
+        config
+            .env
+            .extend(self.env.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+}
+
+impl ProcessConfig {
+    pub fn instance_count(&self) -> u32 {
+        self.instances.unwrap_or(1).max(1)
+    }
+
+    /// Env for the pre-start hook and build, which replicas share. Values using
+    /// `${INSTANCE}` or `${PORT}` are left out when there are replicas, as
+    /// they differ between them.
+    pub fn shared_env(&self) -> HashMap<String, String> {
+        let env = self.instance(1).env;
+        if self.instance_count() == 1 {
+            return env;
+        }
+
+        env.into_iter()
+            .filter(|(k, _)| !self.env[k].contains("${INSTANCE}") && !self.env[k].contains("${PORT}"))
+            .collect()
+    }
+
+    /// Config for one replica of this process. `${INSTANCE}` is the 1-based
+    /// index and `${PORT}` the base `port` offset by the index, so the first
+    /// replica keeps the base port.
+    pub fn instance(&self, index: u32) -> ProcessConfig {
+        let port = self
+            .port
+            .map(|p| p.saturating_add(index.saturating_sub(1) as u16));
+
+        let substitute = |value: &str| {
+            let value = value.replace("${INSTANCE}", &index.to_string());
+            match port {
+                Some(port) => value.replace("${PORT}", &port.to_string()),
+                None => value,
+            }
+        };
+
+        let mut config = self.clone();
+        if self.instances.is_some() || index > 1 {
+            config.name = format!("{}#{}", self.name, index);
+        }
+        config.port = port;
+        config.cmd = self.cmd.iter().map(|x| substitute(x)).collect();
+        config.env = self
+            .env
+            .iter()
+            .map(|(k, v)| (k.clone(), substitute(v)))
+            .collect();
+        config
+    }
+}
+
+impl Config {
+    /// A chain of processes that ends up depending on its first one, e.g.
+    /// `["a", "b", "a"]`, as none of them could ever start
+    pub fn dependency_cycle(&self) -> Option<Vec<String>> {
+        fn visit<'a>(
+            config: &'a Config,
+            name: &'a str,
+            path: &mut Vec<&'a str>,
+            done: &mut Vec<&'a str>,
+        ) -> Option<Vec<String>> {
+            if let Some(i) = path.iter().position(|x| *x == name) {
+                let mut cycle: Vec<String> = path[i..].iter().map(|x| x.to_string()).collect();
+                cycle.push(name.to_string());
+                return Some(cycle);
+            }
+            if done.contains(&name) {
+                return None;
+            }
+
+            path.push(name);
+            let process = config.processes.iter().find(|x| x.name == name);
+            for dep in process.into_iter().flat_map(|x| &x.depends_on) {
+                if let Some(cycle) = visit(config, dep, path, done) {
+                    return Some(cycle);
+                }
+            }
+            path.pop();
+            done.push(name);
+
+            None
+        }
+
+        let mut done = vec![];
+        self.processes
+            .iter()
+            .find_map(|p| visit(self, &p.name, &mut vec![], &mut done))
+    }
+
+    /// Returns the process list with the given profile's selection and
+    /// overrides applied.
+    pub fn resolve(&self, profile: Option<&str>) -> Result<Vec<ProcessConfig>, String> {
+        let Some(profile_name) = profile else {
+            return Ok(self.processes.clone());
+        };
+
+        let profile = self
+            .profiles
+            .get(profile_name)
+            .ok_or_else(|| format!("Unknown profile: {profile_name}"))?;
+
+        let selected = profile.processes.as_ref();
+        let names = selected
+            .into_iter()
+            .flatten()
+            .chain(profile.overrides.keys());
+
+        for name in names {
+            if !self.processes.iter().any(|x| &x.name == name) {
+                return Err(format!(
+                    "Profile {profile_name} references unknown process: {name}"
+                ));
+            }
+        }
+
+        Ok(self
+            .processes
+            .iter()
+            .filter(|x| selected.is_none_or(|s| s.contains(&x.name)))
+            .map(|x| {
+                let mut config = x.clone();
+                if let Some(o) = profile.overrides.get(&x.name) {
+                    o.apply(&mut config);
+                }
+                config
+            })
+            .collect())
+    }
 }
 
 pub fn load_config(path: &str) -> Result<Config, Box<dyn std::error::Error>> {
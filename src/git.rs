@@ -0,0 +1,144 @@
+use std::{
+    collections::HashMap,
+    process::Command,
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+const GIT_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+fn git(cwd: Option<&str>, args: &[&str]) -> Result<String, String> {
+    let mut cmd = Command::new("git");
+    cmd.args(args);
+
+    if let Some(dir) = cwd {
+        cmd.current_dir(dir);
+    }
+
+    let output = cmd.output().map_err(|e| format!("git: {e}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr.lines().next().unwrap_or("failed").trim();
+        return Err(format!("git {}: {}", args[0], message));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Fetches and checks whether the upstream branch has commits that HEAD
+/// doesn't
+pub fn has_upstream_changes(cwd: Option<&str>) -> Result<bool, String> {
+    git(cwd, &["fetch", "--quiet"])?;
+
+    let behind = git(cwd, &["rev-list", "--count", "HEAD..@{upstream}"])?;
+    Ok(behind.parse::<u32>().unwrap_or(0) > 0)
+}
+
+#[derive(Clone, Default)]
+pub struct GitStatus {
+    pub branch: String,
+    pub ahead: u32,
+    pub behind: u32,
+    pub dirty: bool,
+}
+
+impl GitStatus {
+    pub fn display(&self) -> String {
+        let mut out = self.branch.clone();
+
+        if self.ahead > 0 {
+            out.push_str(&format!(" ↑{}", self.ahead));
+        }
+        if self.behind > 0 {
+            out.push_str(&format!(" ↓{}", self.behind));
+        }
+        if self.dirty {
+            out.push_str(" *");
+        }
+
+        out
+    }
+}
+
+/// Branch, ahead/behind counts and dirtiness of the repository at `cwd`, or
+/// `None` if it isn't one
+pub fn status(cwd: Option<&str>) -> Option<GitStatus> {
+    let output = git(cwd, &["status", "--porcelain=v2", "--branch"]).ok()?;
+    let mut status = GitStatus::default();
+
+    for line in output.lines() {
+        if let Some(head) = line.strip_prefix("# branch.head ") {
+            status.branch = head.to_string();
+        } else if let Some(ab) = line.strip_prefix("# branch.ab ") {
+            for part in ab.split_whitespace() {
+                if let Some(n) = part.strip_prefix('+') {
+                    status.ahead = n.parse().unwrap_or(0);
+                } else if let Some(n) = part.strip_prefix('-') {
+                    status.behind = n.parse().unwrap_or(0);
+                }
+            }
+        } else if !line.starts_with('#') {
+            status.dirty = true;
+        }
+    }
+
+    Some(status)
+}
+
+/// Keeps the git status of a set of directories up to date in the background
+pub struct GitWatcher {
+    dirs: Arc<Mutex<Vec<String>>>,
+    statuses: Arc<Mutex<HashMap<String, GitStatus>>>,
+    /// Refreshes right away rather than at the next interval
+    wake: Sender<()>,
+}
+
+impl GitWatcher {
+    pub fn new() -> Self {
+        let dirs: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let statuses = Arc::new(Mutex::new(HashMap::new()));
+
+        let watched = dirs.clone();
+        let results = statuses.clone();
+        let (wake, wakeups) = mpsc::channel();
+
+        thread::spawn(move || {
+            loop {
+                let dirs = watched.lock().unwrap().clone();
+
+                for dir in dirs {
+                    match status(Some(&dir)) {
+                        Some(status) => results.lock().unwrap().insert(dir, status),
+                        None => results.lock().unwrap().remove(&dir),
+                    };
+                }
+
+                // Ends once the watcher is dropped
+                let woken = wakeups.recv_timeout(GIT_REFRESH_INTERVAL);
+                if let Err(RecvTimeoutError::Disconnected) = woken {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            dirs,
+            statuses,
+            wake,
+        }
+    }
+
+    pub fn set_dirs(&self, dirs: Vec<String>) {
+        *self.dirs.lock().unwrap() = dirs;
+        self.wake.send(()).ok();
+    }
+
+    pub fn get(&self, dir: &str) -> Option<GitStatus> {
+        self.statuses.lock().unwrap().get(dir).cloned()
+    }
+}
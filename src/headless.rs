@@ -0,0 +1,58 @@
+use std::{
+    env,
+    io::{self, Write},
+};
+
+use chrono::Local;
+use crossterm::style::{Color, Stylize};
+
+use crate::ansi;
+use crate::managed_process::ManagedProcess;
+use crate::util::Fnv;
+
+const COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Yellow,
+    Color::Green,
+    Color::Magenta,
+    Color::Blue,
+    Color::Red,
+];
+
+/// Prints every finished log line of the process to stdout as
+/// `HH:MM:SS name | line`, with the name padded to `width`
+pub fn echo(p: &ManagedProcess, width: usize) {
+    let name = format!("{:width$}", p.name);
+    let color = (!no_color()).then(|| color_for(&p.name));
+    let strip_ansi = p.strip_ansi;
+
+    p.logs.lock().unwrap().set_echo(move |line| {
+        let prefix = format!("{} {name} |", Local::now().format("%H:%M:%S"));
+        let prefix = match color {
+            Some(color) => prefix.with(color).to_string(),
+            None => prefix,
+        };
+        let line = if strip_ansi {
+            ansi::strip(line)
+        } else {
+            line.to_string()
+        };
+
+        // Ignore errors so a closed pipe doesn't take the process down with it
+        writeln!(io::stdout().lock(), "{prefix} {line}").ok();
+    });
+}
+
+/// Picks a colour from the name so a process keeps its colour across runs
+/// and config changes
+fn color_for(name: &str) -> Color {
+    let mut hash = Fnv::new();
+    hash.write(name.as_bytes());
+
+    COLORS[(hash.finish() % COLORS.len() as u64) as usize]
+}
+
+/// See https://no-color.org
+fn no_color() -> bool {
+    env::var_os("NO_COLOR").is_some_and(|x| !x.is_empty())
+}
@@ -1,7 +1,10 @@
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
 use std::collections::HashMap;
 
-#[derive(Eq, PartialEq, Hash, Clone)]
+use crate::util::keycode_display;
+
+#[derive(Eq, PartialEq, Hash, Clone, Copy)]
 pub enum KeybindType {
     Up,
     Down,
@@ -12,61 +15,539 @@ pub enum KeybindType {
     Quit,
     Escape,
     Update,
+    Profiles,
+    ScaleUp,
+    ScaleDown,
+    UpdateProgress,
+    UpdateAll,
+    UpdateGroup,
+    Help,
+    Palette,
+    Details,
+    Attach,
+    Detach,
+    SendInput,
+    Left,
+    Right,
+    Wrap,
+}
+
+impl KeybindType {
+    /// Name used for the action in the `[keybinds]` config section
+    pub fn config_name(&self) -> &'static str {
+        match self {
+            KeybindType::Up => "up",
+            KeybindType::Down => "down",
+            KeybindType::Restart => "restart",
+            KeybindType::Start => "start",
+            KeybindType::Stop => "stop",
+            KeybindType::Enter => "view_logs",
+            KeybindType::Quit => "quit",
+            KeybindType::Escape => "back",
+            KeybindType::Update => "update",
+            KeybindType::Profiles => "profiles",
+            KeybindType::ScaleUp => "scale_up",
+            KeybindType::ScaleDown => "scale_down",
+            KeybindType::UpdateProgress => "update_progress",
+            KeybindType::UpdateAll => "update_all",
+            KeybindType::UpdateGroup => "update_group",
+            KeybindType::Help => "help",
+            KeybindType::Palette => "palette",
+            KeybindType::Details => "details",
+            KeybindType::Attach => "attach",
+            KeybindType::Detach => "detach",
+            KeybindType::SendInput => "send_input",
+            KeybindType::Left => "left",
+            KeybindType::Right => "right",
+            KeybindType::Wrap => "wrap",
+        }
+    }
 }
 
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Copy, Eq, PartialEq)]
 pub enum KeybindContext {
     Main,
     Logs,
+    /// While attached to a process, where every other key goes to the process
+    Attached,
 }
 
 #[derive(Clone)]
 pub struct Keybind {
     pub t: KeybindType,
     pub name: String,
+    /// Longer explanation shown in the help overlay
+    pub description: String,
     pub context: KeybindContext,
 }
 
 impl Keybind {
-    pub fn new<T: Into<String>>(t: KeybindType, name: T) -> Self {
+    pub fn new<T: Into<String>>(t: KeybindType, name: T, description: T) -> Self {
         Self {
             t,
             name: name.into(),
+            description: description.into(),
             context: KeybindContext::Main,
         }
     }
 
-    pub fn new_logs<T: Into<String>>(t: KeybindType, name: T) -> Self {
+    pub fn new_logs<T: Into<String>>(t: KeybindType, name: T, description: T) -> Self {
         Self {
             t,
             name: name.into(),
+            description: description.into(),
             context: KeybindContext::Logs,
         }
     }
+
+    pub fn new_attached<T: Into<String>>(t: KeybindType, name: T, description: T) -> Self {
+        Self {
+            t,
+            name: name.into(),
+            description: description.into(),
+            context: KeybindContext::Attached,
+        }
+    }
+}
+
+/// A key together with its modifiers, e.g. `ctrl+r`
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        // Shift is already part of the character itself ('U' rather than 'u'),
+        // and crossterm reports Shift+Tab as BackTab with Shift set
+        let (code, modifiers) = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => (code, modifiers - KeyModifiers::SHIFT),
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => {
+                (KeyCode::BackTab, modifiers - KeyModifiers::SHIFT)
+            }
+            _ => (code, modifiers),
+        };
+
+        Self { code, modifiers }
+    }
+
+    pub fn from_event(event: &KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+
+    /// Parses chords like `q`, `ctrl+p`, `alt+enter`, `shift+tab` or `f5`
+    pub fn parse(chord: &str) -> Result<Self, String> {
+        let (modifiers, key) = match chord.strip_suffix("++") {
+            Some(modifiers) => (modifiers, "+"),
+            None => match chord.rsplit_once('+') {
+                Some((modifiers, key)) if !key.is_empty() => (modifiers, key),
+                _ => ("", chord),
+            },
+        };
+
+        let mut mods = KeyModifiers::NONE;
+        for part in modifiers.split('+').filter(|x| !x.is_empty()) {
+            mods |= match part.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("Unknown modifier {part} in key {chord}")),
+            };
+        }
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if mods.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_lowercase().as_str() {
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" | "pgup" => KeyCode::PageUp,
+                "pagedown" | "pgdn" => KeyCode::PageDown,
+                "space" => KeyCode::Char(' '),
+                name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n) if (1..=24).contains(&n) => KeyCode::F(n),
+                    _ => return Err(format!("Unknown key {chord}")),
+                },
+            },
+        };
+
+        Ok(Self::new(code, mods))
+    }
+
+    /// Renders the chord like `Ctrl+Alt+x`
+    pub fn display(&self) -> String {
+        let mut out = String::new();
+
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            out.push_str("Ctrl+");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            out.push_str("Alt+");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            out.push_str("Shift+");
+        }
+
+        out.push_str(&keycode_display(&self.code));
+        out
+    }
+}
+
+/// One key or a list of alternatives for an action in the config
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    fn chords(&self) -> Vec<&str> {
+        match self {
+            KeyList::One(x) => vec![x],
+            KeyList::Many(x) => x.iter().map(|x| x.as_str()).collect(),
+        }
+    }
+}
+
+pub struct Keybinds {
+    binds: Vec<(Keybind, Vec<KeyChord>)>,
 }
 
-pub fn get_keybinds() -> HashMap<KeyCode, Keybind> {
-    HashMap::from([
-        (KeyCode::Up, Keybind::new(KeybindType::Up, "Up")),
-        (KeyCode::Down, Keybind::new(KeybindType::Down, "Down")),
+impl Keybinds {
+    /// Default keybinds with the actions from the config replaced, failing if
+    /// a key ends up bound twice within a context
+    pub fn new(overrides: &HashMap<String, KeyList>) -> Result<Self, String> {
+        let mut binds = default_keybinds();
+
+        for (action, keys) in overrides {
+            let chords = keys
+                .chords()
+                .into_iter()
+                .map(KeyChord::parse)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut found = false;
+            for bind in binds.iter_mut().filter(|x| x.0.t.config_name() == action) {
+                bind.1 = chords.clone();
+                found = true;
+            }
+
+            if !found {
+                return Err(format!("Unknown keybind action: {action}"));
+            }
+        }
+
+        for (i, (a, a_keys)) in binds.iter().enumerate() {
+            for (b, b_keys) in &binds[i + 1..] {
+                if a.context != b.context || a.t == b.t {
+                    continue;
+                }
+
+                if let Some(key) = a_keys.iter().find(|x| b_keys.contains(x)) {
+                    return Err(format!(
+                        "Key {} is bound to both {} and {}",
+                        key.display(),
+                        a.t.config_name(),
+                        b.t.config_name()
+                    ));
+                }
+            }
+        }
+
+        Ok(Self { binds })
+    }
+
+    /// The first bind for the key, looking through the contexts in order
+    /// The bind for the key, searching the contexts in order. Only `actions`
+    /// are considered if given, so a key bound to an action the view ignores
+    /// in an earlier context doesn't hide a later one.
+    pub fn get(
+        &self,
+        contexts: &[KeybindContext],
+        actions: Option<&[KeybindType]>,
+        key: &KeyEvent,
+    ) -> Option<&Keybind> {
+        let chord = KeyChord::from_event(key);
+
+        contexts.iter().find_map(|context| {
+            self.binds
+                .iter()
+                .filter(|x| actions.is_none_or(|a| a.contains(&x.0.t)))
+                .find(|x| x.0.context == *context && x.1.contains(&chord))
+                .map(|x| &x.0)
+        })
+    }
+
+    /// Every action reachable from the contexts, taking the bind from the
+    /// first context that has the action like get() does
+    pub fn for_contexts(&self, contexts: &[KeybindContext]) -> Vec<(Keybind, Vec<KeyChord>)> {
+        let mut out: Vec<(Keybind, Vec<KeyChord>)> = vec![];
+
+        for context in contexts {
+            for bind in self.binds.iter().filter(|x| x.0.context == *context) {
+                if !out.iter().any(|x| x.0.t == bind.0.t) {
+                    out.push(bind.clone());
+                }
+            }
+        }
+
+        out
+    }
+}
+
+fn default_keybinds() -> Vec<(Keybind, Vec<KeyChord>)> {
+    let key = |c| KeyChord::new(c, KeyModifiers::NONE);
+
+    vec![
+        (
+            Keybind::new(KeybindType::Up, "Up", "Select the previous process"),
+            vec![key(KeyCode::Up), key(KeyCode::Char('k'))],
+        ),
+        (
+            Keybind::new(KeybindType::Down, "Down", "Select the next process"),
+            vec![key(KeyCode::Down), key(KeyCode::Char('j'))],
+        ),
+        (
+            Keybind::new(KeybindType::Restart, "Restart", "Stop and start the selected process"),
+            vec![key(KeyCode::Char('r'))],
+        ),
+        (
+            Keybind::new(KeybindType::Start, "Start", "Start the selected process"),
+            vec![key(KeyCode::Char('s'))],
+        ),
+        (
+            Keybind::new(KeybindType::Stop, "Stop", "Stop the selected process"),
+            vec![key(KeyCode::Char('x'))],
+        ),
         (
-            KeyCode::Char('r'),
-            Keybind::new(KeybindType::Restart, "Restart"),
+            Keybind::new(KeybindType::Enter, "View Logs", "Open the logs of the selected process"),
+            vec![key(KeyCode::Enter)],
         ),
         (
-            KeyCode::Char('s'),
-            Keybind::new(KeybindType::Start, "Start"),
+            Keybind::new(KeybindType::Details, "Details", "Show details and exit history of the selected process"),
+            vec![key(KeyCode::Char('i'))],
         ),
-        (KeyCode::Char('x'), Keybind::new(KeybindType::Stop, "Stop")),
         (
-            KeyCode::Enter,
-            Keybind::new(KeybindType::Enter, "View Logs"),
+            Keybind::new(KeybindType::Update, "Update", "Run the update command of the selected process"),
+            vec![key(KeyCode::Char('u'))],
         ),
-        (KeyCode::Char('u'), Keybind::new(KeybindType::Update, "Update")),
-        (KeyCode::Char('q'), Keybind::new(KeybindType::Quit, "Quit")),
         (
-            KeyCode::Esc,
-            Keybind::new_logs(KeybindType::Escape, "Escape"),
+            Keybind::new(KeybindType::UpdateAll, "Update All", "Update every process with an update command"),
+            vec![key(KeyCode::Char('U'))],
         ),
-    ])
+        (
+            Keybind::new(KeybindType::UpdateGroup, "Update Group", "Update every process in the selected group"),
+            vec![key(KeyCode::Char('g'))],
+        ),
+        (
+            Keybind::new(KeybindType::UpdateProgress, "Update Progress", "Show the update steps of the selected process"),
+            vec![key(KeyCode::Char('v'))],
+        ),
+        (
+            Keybind::new(KeybindType::Profiles, "Profiles", "Switch to another profile"),
+            vec![key(KeyCode::Char('p'))],
+        ),
+        (
+            Keybind::new(KeybindType::ScaleUp, "Scale Up", "Start another replica of the selected process"),
+            vec![key(KeyCode::Char('+'))],
+        ),
+        (
+            Keybind::new(KeybindType::ScaleDown, "Scale Down", "Stop the last replica of the selected process"),
+            vec![key(KeyCode::Char('-'))],
+        ),
+        (
+            Keybind::new(KeybindType::Help, "Help", "Show this help"),
+            vec![key(KeyCode::Char('?'))],
+        ),
+        (
+            Keybind::new(KeybindType::Palette, "Commands", "Open the command palette"),
+            vec![key(KeyCode::Char(':')), KeyChord::new(KeyCode::Char('p'), KeyModifiers::CONTROL)],
+        ),
+        (
+            Keybind::new(KeybindType::Quit, "Quit", "Stop every process and exit"),
+            vec![key(KeyCode::Char('q'))],
+        ),
+        (
+            Keybind::new_logs(KeybindType::Escape, "Back", "Go back to the process list"),
+            vec![key(KeyCode::Esc)],
+        ),
+        (
+            Keybind::new_logs(KeybindType::Up, "Up", "Scroll up"),
+            vec![key(KeyCode::Up), key(KeyCode::Char('k'))],
+        ),
+        (
+            Keybind::new_logs(KeybindType::Down, "Down", "Scroll down"),
+            vec![key(KeyCode::Down), key(KeyCode::Char('j'))],
+        ),
+        (
+            Keybind::new_logs(KeybindType::Left, "Left", "Scroll left when not wrapping"),
+            vec![key(KeyCode::Left), key(KeyCode::Char('h'))],
+        ),
+        (
+            Keybind::new_logs(KeybindType::Right, "Right", "Scroll right when not wrapping"),
+            vec![key(KeyCode::Right), key(KeyCode::Char('l'))],
+        ),
+        (
+            Keybind::new_logs(KeybindType::Wrap, "Wrap", "Toggle wrapping long lines"),
+            vec![key(KeyCode::Char('w'))],
+        ),
+        (
+            Keybind::new_logs(KeybindType::Help, "Help", "Show this help"),
+            vec![key(KeyCode::Char('?'))],
+        ),
+        (
+            Keybind::new_logs(KeybindType::Palette, "Commands", "Open the command palette"),
+            vec![key(KeyCode::Char(':')), KeyChord::new(KeyCode::Char('p'), KeyModifiers::CONTROL)],
+        ),
+        (
+            Keybind::new_logs(KeybindType::Attach, "Attach", "Send every key to the process until detached"),
+            vec![key(KeyCode::Char('a'))],
+        ),
+        (
+            Keybind::new_logs(KeybindType::SendInput, "Send Input", "Type a line to send to the process"),
+            vec![key(KeyCode::Char('i'))],
+        ),
+        (
+            Keybind::new_attached(KeybindType::Detach, "Detach", "Stop sending keys to the process"),
+            vec![KeyChord::new(KeyCode::Char('q'), KeyModifiers::CONTROL)],
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(code: KeyCode, modifiers: KeyModifiers) -> KeyChord {
+        KeyChord::new(code, modifiers)
+    }
+
+    fn event(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    fn overrides(pairs: &[(&str, &[&str])]) -> HashMap<String, KeyList> {
+        pairs
+            .iter()
+            .map(|(action, keys)| {
+                let keys = keys.iter().map(|x| x.to_string()).collect();
+                (action.to_string(), KeyList::Many(keys))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_single_keys() {
+        assert_eq!(KeyChord::parse("q"), Ok(chord(KeyCode::Char('q'), KeyModifiers::NONE)));
+        assert_eq!(KeyChord::parse("+"), Ok(chord(KeyCode::Char('+'), KeyModifiers::NONE)));
+        assert_eq!(KeyChord::parse("space"), Ok(chord(KeyCode::Char(' '), KeyModifiers::NONE)));
+        assert_eq!(KeyChord::parse("F5"), Ok(chord(KeyCode::F(5), KeyModifiers::NONE)));
+        assert_eq!(KeyChord::parse("PageUp"), Ok(chord(KeyCode::PageUp, KeyModifiers::NONE)));
+        assert_eq!(KeyChord::parse("esc"), Ok(chord(KeyCode::Esc, KeyModifiers::NONE)));
+    }
+
+    #[test]
+    fn parses_modifiers() {
+        assert_eq!(
+            KeyChord::parse("ctrl+p"),
+            Ok(chord(KeyCode::Char('p'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            KeyChord::parse("Ctrl+Alt+Enter"),
+            Ok(chord(KeyCode::Enter, KeyModifiers::CONTROL | KeyModifiers::ALT))
+        );
+        assert_eq!(
+            KeyChord::parse("ctrl++"),
+            Ok(chord(KeyCode::Char('+'), KeyModifiers::CONTROL))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_modifiers() {
+        assert!(KeyChord::parse("hyper+x").is_err());
+        assert!(KeyChord::parse("f25").is_err());
+        assert!(KeyChord::parse("nope").is_err());
+    }
+
+    #[test]
+    fn shift_is_part_of_characters() {
+        let upper = chord(KeyCode::Char('U'), KeyModifiers::NONE);
+
+        assert_eq!(KeyChord::parse("shift+u"), Ok(upper));
+        assert_eq!(KeyChord::parse("U"), Ok(upper));
+        // Terminals report Shift along with the uppercase character
+        assert_eq!(chord(KeyCode::Char('U'), KeyModifiers::SHIFT), upper);
+        assert_eq!(
+            KeyChord::parse("ctrl+shift+u"),
+            Ok(chord(KeyCode::Char('U'), KeyModifiers::CONTROL))
+        );
+    }
+
+    #[test]
+    fn shift_tab_is_backtab() {
+        let backtab = chord(KeyCode::BackTab, KeyModifiers::NONE);
+
+        assert_eq!(KeyChord::parse("shift+tab"), Ok(backtab));
+        assert_eq!(KeyChord::parse("backtab"), Ok(backtab));
+        // What crossterm reports for Shift+Tab
+        assert_eq!(KeyChord::from_event(&event(KeyCode::BackTab, KeyModifiers::SHIFT)), backtab);
+        assert_eq!(backtab.display(), "Shift+Tab");
+    }
+
+    #[test]
+    fn displays_chords() {
+        assert_eq!(KeyChord::parse("ctrl+alt+x").unwrap().display(), "Ctrl+Alt+x");
+        assert_eq!(KeyChord::parse("shift+up").unwrap().display(), "Shift+↑");
+        assert_eq!(KeyChord::parse("f5").unwrap().display(), "F5");
+    }
+
+    #[test]
+    fn overrides_replace_default_keys() {
+        let binds = Keybinds::new(&overrides(&[("stop", &["ctrl+x", "X"])])).unwrap();
+        let main = [KeybindContext::Main];
+
+        let stop = |code, modifiers| binds.get(&main, None, &event(code, modifiers)).map(|x| x.t);
+        assert!(stop(KeyCode::Char('x'), KeyModifiers::CONTROL) == Some(KeybindType::Stop));
+        assert!(stop(KeyCode::Char('X'), KeyModifiers::SHIFT) == Some(KeybindType::Stop));
+        assert!(stop(KeyCode::Char('x'), KeyModifiers::NONE).is_none());
+    }
+
+    #[test]
+    fn skips_actions_the_view_ignores() {
+        let binds = Keybinds::new(&overrides(&[("wrap", &["enter"])])).unwrap();
+        let enter = event(KeyCode::Enter, KeyModifiers::NONE);
+        let contexts = [KeybindContext::Logs, KeybindContext::Main];
+        let actions = [KeybindType::Escape, KeybindType::Enter];
+
+        let get = |actions| binds.get(&contexts, actions, &enter).map(|x| x.t);
+        assert!(get(None) == Some(KeybindType::Wrap));
+        assert!(get(Some(&actions[..])) == Some(KeybindType::Enter));
+        assert!(get(Some(&actions[..1])).is_none());
+    }
+
+    #[test]
+    fn rejects_conflicts_within_a_context() {
+        let err = Keybinds::new(&overrides(&[("stop", &["k"])])).err().unwrap();
+        assert_eq!(err, "Key k is bound to both up and stop");
+
+        // The same key may be used in different contexts
+        assert!(Keybinds::new(&overrides(&[("attach", &["r"])])).is_ok());
+        assert!(Keybinds::new(&overrides(&[("nope", &["z"])])).is_err());
+    }
 }
@@ -0,0 +1,336 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Read},
+    sync::{Arc, Mutex},
+};
+
+pub type Logs = Arc<Mutex<LogBuffer>>;
+type Echo = Arc<dyn Fn(&str) + Send + Sync>;
+
+const MAX_LOG_LINES: usize = 2000;
+/// Longer lines are folded onto several lines, in bytes
+const MAX_LINE_LEN: usize = 4096;
+
+/// The last MAX_LOG_LINES lines of output. Lines have increasing sequence
+/// numbers so an unfinished line can be updated after more lines were added.
+#[derive(Default)]
+pub struct LogBuffer {
+    lines: VecDeque<String>,
+    /// Sequence number of `lines[0]`
+    first: u64,
+    /// Called with every finished line, e.g. to print it in headless mode
+    echo: Option<Echo>,
+}
+
+impl LogBuffer {
+    pub fn set_echo(&mut self, echo: impl Fn(&str) + Send + Sync + 'static) {
+        self.echo = Some(Arc::new(echo));
+    }
+
+    /// Adds a line, returning its sequence number so it can be updated with
+    /// `replace` while it's still being written
+    pub fn push(&mut self, line: String) -> u64 {
+        let seq = self.first + self.lines.len() as u64;
+        self.lines.push_back(line);
+
+        if self.lines.len() > MAX_LOG_LINES {
+            self.lines.pop_front();
+            self.first += 1;
+        }
+
+        seq
+    }
+
+    /// Replaces a line unless it has already been dropped
+    pub fn replace(&mut self, seq: u64, line: String) {
+        if let Some(old) = seq
+            .checked_sub(self.first)
+            .and_then(|i| self.lines.get_mut(i as usize))
+        {
+            *old = line;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &String> + ExactSizeIterator {
+        self.lines.iter()
+    }
+}
+
+/// Splits raw output into log lines. A `\r` not followed by `\n` makes the
+/// rest of the line overwrite it like a terminal would for progress bars, and
+/// unfinished lines are shown straight away and updated as they grow.
+struct LineReader<'a> {
+    logs: &'a Mutex<LogBuffer>,
+    line: Vec<u8>,
+    /// The unfinished line currently shown in the logs
+    shown: Option<u64>,
+    carriage_return: bool,
+}
+
+impl LineReader<'_> {
+    fn feed(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            match b {
+                b'\n' => {
+                    self.carriage_return = false;
+                    self.finish_line();
+                }
+                b'\r' => self.carriage_return = true,
+                _ => {
+                    if self.carriage_return {
+                        self.carriage_return = false;
+                        self.line.clear();
+                    }
+
+                    self.line.push(b);
+                    if self.line.len() >= MAX_LINE_LEN {
+                        self.fold();
+                    }
+                }
+            }
+        }
+
+        if !self.line.is_empty() {
+            self.show(String::from_utf8_lossy(&self.line).into_owned());
+        }
+    }
+
+    fn finish_line(&mut self) {
+        let line = String::from_utf8_lossy(&self.line).into_owned();
+        self.line.clear();
+
+        finish(self.logs, self.shown.take(), line);
+    }
+
+    /// Ends the line early, keeping a trailing incomplete UTF-8 character
+    /// for the next line rather than splitting it
+    fn fold(&mut self) {
+        let len = self.line.len();
+        let mut at = len;
+
+        if let Some(start) = (len.saturating_sub(4)..len)
+            .rev()
+            .find(|&i| self.line[i] & 0xc0 != 0x80)
+            && start > 0
+            && char_len(self.line[start]) > len - start
+        {
+            at = start;
+        }
+
+        let rest = self.line.split_off(at);
+        self.finish_line();
+        self.line = rest;
+    }
+
+    fn show(&mut self, line: String) {
+        let mut logs = self.logs.lock().unwrap();
+
+        match self.shown {
+            Some(seq) => logs.replace(seq, line),
+            None => self.shown = Some(logs.push(line)),
+        }
+    }
+}
+
+/// Adds a finished line to the logs
+pub fn push(logs: &Mutex<LogBuffer>, line: String) {
+    finish(logs, None, line);
+}
+
+/// Adds a finished line, or replaces the unfinished line `seq` with it. The
+/// echo is called after the lock is released so a slow stdout in headless
+/// mode only holds up the thread writing this line.
+fn finish(logs: &Mutex<LogBuffer>, seq: Option<u64>, line: String) {
+    let mut buffer = logs.lock().unwrap();
+    let echo = buffer.echo.clone().map(|echo| (echo, line.clone()));
+
+    match seq {
+        Some(seq) => buffer.replace(seq, line),
+        None => {
+            buffer.push(line);
+        }
+    }
+    drop(buffer);
+
+    if let Some((echo, line)) = echo {
+        echo(&line);
+    }
+}
+
+/// Reads output into the logs until the stream ends
+pub fn read_into(mut stream: impl Read, logs: &Mutex<LogBuffer>) {
+    let mut reader = LineReader {
+        logs,
+        line: vec![],
+        shown: None,
+        carriage_return: false,
+    };
+    let mut buf = [0; 4096];
+
+    loop {
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => reader.feed(&buf[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            // A pty reports EIO once the process exits
+            Err(_) => break,
+        }
+    }
+
+    if !reader.line.is_empty() {
+        reader.finish_line();
+    }
+}
+
+/// The length of a UTF-8 character from its first byte
+fn char_len(first: u8) -> usize {
+    match first {
+        0xf0.. => 4,
+        0xe0.. => 3,
+        0xc0.. => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reader(logs: &Mutex<LogBuffer>) -> LineReader<'_> {
+        LineReader {
+            logs,
+            line: vec![],
+            shown: None,
+            carriage_return: false,
+        }
+    }
+
+    fn lines(logs: &Mutex<LogBuffer>) -> Vec<String> {
+        logs.lock().unwrap().iter().cloned().collect()
+    }
+
+    #[test]
+    fn splits_lines() {
+        let logs = Mutex::default();
+        read_into(&b"one\ntwo\r\n\nthree"[..], &logs);
+
+        assert_eq!(lines(&logs), ["one", "two", "", "three"]);
+    }
+
+    #[test]
+    fn carriage_return_overwrites_the_line() {
+        let logs = Mutex::default();
+        read_into(&b"10%\r20%\r100%\ndone\n"[..], &logs);
+
+        assert_eq!(lines(&logs), ["100%", "done"]);
+    }
+
+    #[test]
+    fn carriage_return_across_chunks() {
+        let logs = Mutex::default();
+        let mut reader = reader(&logs);
+
+        reader.feed(b"10%\r");
+        assert_eq!(lines(&logs), ["10%"]);
+        reader.feed(b"20");
+        assert_eq!(lines(&logs), ["20"]);
+        reader.feed(b"%\r");
+        reader.feed(b"\n");
+        assert_eq!(lines(&logs), ["20%"]);
+    }
+
+    #[test]
+    fn shows_unfinished_lines() {
+        let logs = Mutex::default();
+        let mut reader = reader(&logs);
+
+        reader.feed(b"hel");
+        assert_eq!(lines(&logs), ["hel"]);
+        reader.feed(b"lo\nwor");
+        assert_eq!(lines(&logs), ["hello", "wor"]);
+        reader.feed(b"ld\n");
+        assert_eq!(lines(&logs), ["hello", "world"]);
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced() {
+        let logs = Mutex::default();
+        read_into(&b"a\xffb\n"[..], &logs);
+
+        assert_eq!(lines(&logs), ["a\u{fffd}b"]);
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let logs = Mutex::default();
+        let line = "a".repeat(MAX_LINE_LEN + 3);
+        read_into(line.as_bytes(), &logs);
+
+        assert_eq!(lines(&logs), ["a".repeat(MAX_LINE_LEN), "aaa".to_string()]);
+    }
+
+    #[test]
+    fn folding_keeps_characters_whole() {
+        let logs = Mutex::default();
+        let start = "a".repeat(MAX_LINE_LEN - 2);
+        let mut reader = reader(&logs);
+
+        // Split the character between chunks too
+        let bytes = format!("{start}€b\n");
+        let (first, second) = bytes.as_bytes().split_at(MAX_LINE_LEN - 1);
+        reader.feed(first);
+        reader.feed(second);
+
+        assert_eq!(lines(&logs), [start, "€b".to_string()]);
+    }
+
+    #[test]
+    fn keeps_the_last_lines() {
+        let mut logs = LogBuffer::default();
+        let dropped = logs.push("first".to_string());
+        for i in 0..MAX_LOG_LINES {
+            logs.push(i.to_string());
+        }
+
+        assert_eq!(logs.len(), MAX_LOG_LINES);
+        assert_eq!(logs.iter().next().unwrap(), "0");
+
+        logs.replace(dropped, "replaced".to_string());
+        assert!(logs.iter().all(|x| x != "replaced"));
+    }
+
+    #[test]
+    fn echoes_finished_lines() {
+        let echoed = Arc::new(Mutex::new(vec![]));
+        let logs = Mutex::new(LogBuffer::default());
+        let sink = echoed.clone();
+        logs.lock()
+            .unwrap()
+            .set_echo(move |line| sink.lock().unwrap().push(line.to_string()));
+
+        let mut reader = reader(&logs);
+        reader.feed(b"10%\r20%");
+        reader.feed(b"\r30%\nnext");
+        assert_eq!(*echoed.lock().unwrap(), ["30%"]);
+
+        read_into(&b"last\n"[..], &logs);
+        assert_eq!(*echoed.lock().unwrap(), ["30%", "last"]);
+    }
+
+    #[test]
+    fn echoes_without_holding_the_lock() {
+        let logs: Logs = Arc::default();
+        let weak = Arc::downgrade(&logs);
+        logs.lock()
+            .unwrap()
+            .set_echo(move |_| assert!(weak.upgrade().unwrap().try_lock().is_ok()));
+
+        push(&logs, "pushed".to_string());
+        read_into(&b"read\n"[..], &logs);
+        assert_eq!(lines(&logs), ["pushed", "read"]);
+    }
+}
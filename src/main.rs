@@ -1,67 +1,322 @@
+mod ansi;
+mod build;
+mod cli;
 mod config;
+mod git;
+mod headless;
 mod keybinds;
+mod logs;
 mod managed_process;
+mod palette;
+mod pty;
+mod resources;
+mod schedule;
+mod update;
 mod util;
+mod watch;
 
 use std::{
+    collections::VecDeque,
     io,
     process::exit,
     sync::atomic::{AtomicBool, Ordering},
-    time::Duration,
+    thread,
+    time::{Duration, Instant},
 };
 
+use chrono::Local;
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 
 use ratatui::{
     backend::CrosstermBackend,
+    layout::{Constraint, Flex, Layout, Position, Rect},
     prelude::{Line, Span, Stylize},
     style::{Modifier, Style},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    widgets::{
+        Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Sparkline, Table,
+        TableState, Wrap,
+    },
     Terminal,
 };
 
-use crate::config::load_config;
-use crate::keybinds::{get_keybinds, Keybind, KeybindContext, KeybindType};
-use crate::managed_process::ManagedProcess;
-use crate::util::{format_duration, keycode_display};
+use crate::cli::{parse_args, Args};
+use crate::config::{load_config, parse_bytes, Config, LimitAction, ProcessConfig, ProcessKind};
+use crate::keybinds::{KeyChord, Keybind, KeybindContext, KeybindType, Keybinds};
+use crate::git::GitWatcher;
+use crate::managed_process::{
+    cancel_stop_hooks, push_line, ManagedProcess, Readiness, UpdateEvent, UpdateOutcome,
+};
+use crate::palette::{load_history, record_history, Command, Palette, PaletteAction};
+use crate::resources::ProcTable;
+use crate::schedule::Schedule;
+use crate::update::StepState;
+use crate::util::{format_bytes, format_duration, format_exit, format_next_run};
 
 static RUNNING: AtomicBool = AtomicBool::new(true);
+/// Set by Ctrl+C while exiting, to stop waiting for stop hooks
+static FORCE_EXIT: AtomicBool = AtomicBool::new(false);
+
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+const SCROLL_LINES: u16 = 3;
+const SCROLL_COLUMNS: u16 = 8;
+const USAGE_INTERVAL: Duration = Duration::from_secs(1);
+const HEADLESS_TICK: Duration = Duration::from_millis(250);
+const STOP_POLL: Duration = Duration::from_millis(50);
 
 enum View {
     List,
     Logs,
     QuitConfirm,
+    Profiles,
+    Update,
+    Detail,
+}
+
+impl View {
+    /// Keybind contexts searched for keys in this view, in order
+    fn contexts(&self) -> &'static [KeybindContext] {
+        match self {
+            View::List => &[KeybindContext::Main],
+            View::Logs => &[KeybindContext::Logs],
+            View::Profiles | View::Update | View::Detail => {
+                &[KeybindContext::Logs, KeybindContext::Main]
+            }
+            View::QuitConfirm => &[],
+        }
+    }
+
+    /// Actions this view responds to, `None` meaning everything in its contexts
+    fn actions(&self) -> Option<&'static [KeybindType]> {
+        match self {
+            View::Profiles => Some(&[
+                KeybindType::Escape,
+                KeybindType::Up,
+                KeybindType::Down,
+                KeybindType::Enter,
+                KeybindType::Help,
+            ]),
+            View::Update => Some(&[KeybindType::Escape, KeybindType::Update, KeybindType::Help]),
+            View::Detail => Some(&[
+                KeybindType::Escape,
+                KeybindType::Up,
+                KeybindType::Down,
+                KeybindType::Help,
+                KeybindType::Palette,
+            ]),
+            _ => None,
+        }
+    }
+
+    /// Name and description for actions that mean something else in this view
+    fn describe(&self, t: KeybindType) -> Option<(&'static str, &'static str)> {
+        match (self, t) {
+            (View::Profiles, KeybindType::Up) => Some(("Up", "Select the previous profile")),
+            (View::Profiles, KeybindType::Down) => Some(("Down", "Select the next profile")),
+            (View::Profiles, KeybindType::Enter) => Some(("Switch", "Switch to the selected profile")),
+            (View::Update, KeybindType::Update) => Some(("Run", "Run the update again")),
+            _ => None,
+        }
+    }
 }
 
 struct App {
     processes: Vec<ManagedProcess>,
-    state: ListState,
+    state: TableState,
     view: View,
+    /// First log line shown, so it stays at the top when toggling wrap
     log_scroll: u16,
+    /// Columns scrolled right in the Logs view when not wrapping
+    log_hscroll: u16,
+    log_wrap: bool,
+    detail_scroll: u16,
+
+    config: Config,
+    args: Args,
+    profile: Option<String>,
+    resolved: Vec<ProcessConfig>,
+    profile_state: ListState,
+    update_queue: VecDeque<String>,
+    git: GitWatcher,
+    keybinds: Keybinds,
+    /// Whether the help overlay is shown on top of the current view
+    help: bool,
+    palette: Option<Palette>,
+    /// Labels of commands run from the palette, most recent first
+    palette_history: Vec<String>,
+    /// Clickable keybind hints from the last draw
+    buttons: Vec<(Rect, KeybindType)>,
+    /// When and which row was last clicked, to detect double clicks
+    last_click: Option<(Instant, usize)>,
+    last_usage_sample: Option<Instant>,
+    /// Whether keys in the Logs view go to the selected process
+    attached: bool,
+    /// Line being typed in the Logs view's send input prompt
+    input_prompt: Option<String>,
+    /// Removed processes whose stop hooks still run, dropped once stopped
+    retired: Vec<ManagedProcess>,
+    /// Replacements of retired processes, started once those stopped
+    deferred_starts: Vec<String>,
 }
 
 impl App {
-    fn new(processes: Vec<ManagedProcess>) -> Self {
-        let mut state = ListState::default();
+    fn new(config: Config, args: Args) -> Result<Self, String> {
+        let profile = args.profile.clone();
+        let resolved = config.resolve(profile.as_deref())?;
+        let keybinds = Keybinds::new(&config.keybinds)?;
+
+        let mut state = TableState::default();
         state.select(Some(0));
 
-        Self {
-            processes,
+        let app = Self {
+            processes: resolved.iter().flat_map(instances).collect(),
             state,
             view: View::List,
             log_scroll: 0,
+            log_hscroll: 0,
+            log_wrap: false,
+            detail_scroll: 0,
+            config,
+            args,
+            profile,
+            resolved,
+            profile_state: ListState::default(),
+            update_queue: VecDeque::new(),
+            git: GitWatcher::new(),
+            keybinds,
+            help: false,
+            palette: None,
+            palette_history: load_history(),
+            buttons: vec![],
+            last_click: None,
+            last_usage_sample: None,
+            attached: false,
+            input_prompt: None,
+            retired: vec![],
+            deferred_starts: vec![],
+        };
+        app.watch_git_dirs();
+
+        Ok(app)
+    }
+
+    fn watch_git_dirs(&self) {
+        let mut dirs: Vec<String> = self
+            .processes
+            .iter()
+            .map(|p| p.cwd.clone().unwrap_or_else(|| ".".into()))
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+
+        self.git.set_dirs(dirs);
+    }
+
+    /// The keybinds usable in the current view
+    fn view_keybinds(&self) -> Vec<(Keybind, Vec<KeyChord>)> {
+        let actions = self.view.actions();
+
+        self.keybinds
+            .for_contexts(self.view.contexts())
+            .into_iter()
+            .filter(|x| actions.is_none_or(|a| a.contains(&x.0.t)))
+            .map(|(mut bind, keys)| {
+                if let Some((name, description)) = self.view.describe(bind.t) {
+                    bind.name = name.into();
+                    bind.description = description.into();
+                }
+                (bind, keys)
+            })
+            .collect()
+    }
+
+    fn open_palette(&mut self) {
+        self.help = false;
+        self.palette = Some(Palette::new(self.commands(), &self.palette_history));
+    }
+
+    /// Everything the command palette can run right now
+    fn commands(&self) -> Vec<Command> {
+        let key = |label: String, t, process: &str| {
+            Command::new(label, PaletteAction::Key(t, Some(process.to_string())))
+        };
+        let mut commands = vec![];
+
+        for p in &self.processes {
+            commands.push(key(format!("start {}", p.name), KeybindType::Start, &p.name));
+            commands.push(key(format!("stop {}", p.name), KeybindType::Stop, &p.name));
+            commands.push(key(format!("restart {}", p.name), KeybindType::Restart, &p.name));
+            commands.push(key(format!("show logs {}", p.name), KeybindType::Enter, &p.name));
+            commands.push(key(format!("show details {}", p.name), KeybindType::Details, &p.name));
+
+            if p.has_update_command() {
+                commands.push(key(format!("update {}", p.name), KeybindType::Update, &p.name));
+                commands.push(key(
+                    format!("show update progress {}", p.name),
+                    KeybindType::UpdateProgress,
+                    &p.name,
+                ));
+            }
+
+            // Scaling works on the whole process, so only offer it once
+            if p.instance == 1 {
+                commands.push(key(format!("scale up {}", p.base_name), KeybindType::ScaleUp, &p.name));
+                commands.push(key(
+                    format!("scale down {}", p.base_name),
+                    KeybindType::ScaleDown,
+                    &p.name,
+                ));
+            }
+        }
+
+        commands.push(Command::new("update all", PaletteAction::Key(KeybindType::UpdateAll, None)));
+
+        let mut groups: Vec<(&str, &str)> = vec![];
+        for p in &self.processes {
+            if let Some(ref group) = p.group
+                && !groups.iter().any(|x| x.0 == group)
+            {
+                groups.push((group, &p.name));
+            }
+        }
+        for (group, process) in groups {
+            commands.push(key(format!("update all {group}"), KeybindType::UpdateGroup, process));
+        }
+
+        for profile in self.profile_names() {
+            let label = match profile {
+                Some(ref name) => format!("switch profile {name}"),
+                None => "clear profile".to_string(),
+            };
+            commands.push(Command::new(label, PaletteAction::Profile(profile)));
         }
+
+        commands.push(Command::new("help", PaletteAction::Key(KeybindType::Help, None)));
+        commands.push(Command::new("quit", PaletteAction::Key(KeybindType::Quit, None)));
+
+        commands
     }
 
     fn selected(&self) -> usize {
         self.state.selected().unwrap_or(0)
     }
 
+    fn selected_process(&mut self) -> Option<&mut ManagedProcess> {
+        let i = self.selected();
+        self.processes.get_mut(i)
+    }
+
     fn next(&mut self) {
+        if self.processes.is_empty() {
+            return;
+        }
+
         let i = self.selected();
         let next = if i + 1 >= self.processes.len() {
             0
@@ -72,6 +327,10 @@ impl App {
     }
 
     fn previous(&mut self) {
+        if self.processes.is_empty() {
+            return;
+        }
+
         let i = self.selected();
         let prev = if i == 0 {
             self.processes.len() - 1
@@ -80,6 +339,402 @@ impl App {
         };
         self.state.select(Some(prev));
     }
+
+    fn start_autostart(&mut self) {
+        for p in &mut self.processes {
+            if self.args.should_start(&p.base_name, p.autostart) {
+                p.start();
+            }
+        }
+    }
+
+    /// Profile names as shown in the profile menu, `None` being the plain config
+    fn profile_names(&self) -> Vec<Option<String>> {
+        std::iter::once(None)
+            .chain(self.config.profiles.keys().cloned().map(Some))
+            .collect()
+    }
+
+    fn open_profiles(&mut self) {
+        let current = self
+            .profile_names()
+            .iter()
+            .position(|x| *x == self.profile)
+            .unwrap_or(0);
+        self.profile_state.select(Some(current));
+        self.view = View::Profiles;
+    }
+
+    /// Switches to another profile, only stopping and starting the processes
+    /// that were added, removed or changed by it.
+    fn switch_profile(&mut self, profile: Option<String>) {
+        let Ok(resolved) = self.config.resolve(profile.as_deref()) else {
+            return;
+        };
+
+        // Stop removed processes first so their ports are free again
+        let (mut old, removed): (Vec<_>, Vec<_>) = std::mem::take(&mut self.processes)
+            .into_iter()
+            .partition(|p| resolved.iter().any(|x| x.name == p.base_name));
+
+        for p in removed {
+            self.retire(p);
+        }
+
+        for config in &resolved {
+            let unchanged = self.resolved.iter().any(|x| x == config);
+            let (mut existing, rest): (Vec<_>, Vec<_>) =
+                old.into_iter().partition(|p| p.base_name == config.name);
+            old = rest;
+
+            if existing.is_empty() {
+                for mut process in instances(config) {
+                    if self.args.should_start(&process.base_name, process.autostart) {
+                        process.start();
+                    }
+                    self.processes.push(process);
+                }
+            } else if unchanged {
+                self.processes.append(&mut existing);
+            } else {
+                let was_running = existing.iter().any(|p| p.child.is_some());
+
+                for mut process in instances(config) {
+                    if let Some(p) = existing.iter().find(|p| p.instance == process.instance) {
+                        process.logs = p.logs.clone();
+                    }
+                    if was_running {
+                        self.deferred_starts.push(process.name.clone());
+                    }
+                    self.processes.push(process);
+                }
+
+                for p in existing {
+                    self.retire(p);
+                }
+            }
+        }
+
+        self.profile = profile;
+        self.resolved = resolved;
+        self.watch_git_dirs();
+
+        self.clamp_selection();
+
+        // The views of a single process have nothing left to show
+        if self.processes.is_empty() {
+            self.view = View::List;
+            self.attached = false;
+            self.input_prompt = None;
+        }
+    }
+
+    /// Starts another replica of the selected process
+    fn scale_up(&mut self) {
+        let Some(base_name) = self.selected_process().map(|p| p.base_name.clone()) else {
+            return;
+        };
+        let Some(config) = self.resolved.iter().find(|x| x.name == base_name) else {
+            return;
+        };
+
+        let last = self
+            .processes
+            .iter()
+            .rposition(|p| p.base_name == base_name)
+            .unwrap_or(0);
+        let instance = self.processes[last].instance + 1;
+
+        let mut process = ManagedProcess::new(config, instance);
+        process.start();
+        self.processes.insert(last + 1, process);
+    }
+
+    /// Stops and removes the last replica of the selected process, keeping at
+    /// least one
+    fn scale_down(&mut self) {
+        let Some(base_name) = self.selected_process().map(|p| p.base_name.clone()) else {
+            return;
+        };
+
+        if self.processes.iter().filter(|p| p.base_name == base_name).count() <= 1 {
+            return;
+        }
+
+        if let Some(last) = self.processes.iter().rposition(|p| p.base_name == base_name) {
+            let process = self.processes.remove(last);
+            self.retire(process);
+        }
+
+        self.clamp_selection();
+    }
+
+    /// Stops a process that is being removed, keeping it around until its
+    /// stop hooks finished
+    fn retire(&mut self, mut process: ManagedProcess) {
+        process.stop();
+        if process.is_stopping() {
+            self.retired.push(process);
+        }
+    }
+
+    /// Drops retired processes once stopped and then starts the processes
+    /// replacing them, so they don't run side by side
+    fn poll_retired(&mut self) {
+        for p in &mut self.retired {
+            p.status();
+        }
+        self.retired.retain(|p| p.is_stopping());
+
+        let retired = &self.retired;
+        let (ready, deferred): (Vec<_>, Vec<_>) = std::mem::take(&mut self.deferred_starts)
+            .into_iter()
+            .partition(|name| !retired.iter().any(|p| &p.name == name));
+        self.deferred_starts = deferred;
+
+        for name in ready {
+            if let Some(p) = self.processes.iter_mut().find(|p| p.name == name) {
+                p.start();
+            }
+        }
+    }
+
+    /// Stops every process as the app is exiting. Their stop hooks run at the
+    /// same time and are waited for unless Ctrl+C is pressed again.
+    fn stop_all(&mut self) {
+        for p in self.processes.iter_mut().chain(&mut self.retired) {
+            p.stop();
+        }
+
+        let mut waiting: Vec<&mut ManagedProcess> = self
+            .processes
+            .iter_mut()
+            .chain(&mut self.retired)
+            .filter(|p| p.is_stopping())
+            .collect();
+
+        if !waiting.is_empty() {
+            let names: Vec<&str> = waiting.iter().map(|p| p.name.as_str()).collect();
+            println!("Waiting for stop hooks of {}, Ctrl+C to skip them", names.join(", "));
+        }
+
+        let mut forced = false;
+        while !waiting.is_empty() {
+            if !forced && FORCE_EXIT.load(Ordering::Relaxed) {
+                forced = true;
+                cancel_stop_hooks();
+                for p in &mut waiting {
+                    p.kill();
+                }
+            }
+
+            thread::sleep(STOP_POLL);
+            for p in &mut waiting {
+                p.status();
+            }
+            waiting.retain(|p| p.is_stopping());
+        }
+    }
+
+    /// Queues an update for every process (in `group` if given) that has an
+    /// update command, once per base process, see tick()
+    fn update_all(&mut self, group: Option<&str>) {
+        for p in &self.processes {
+            let in_group = group.is_none_or(|g| p.group.as_deref() == Some(g));
+
+            if in_group
+                && p.has_update_command()
+                && !self.is_updating(&p.base_name)
+                && !self.update_queue.contains(&p.base_name)
+            {
+                self.update_queue.push_back(p.base_name.clone());
+            }
+        }
+    }
+
+    /// The replica that runs updates for the process at `i`, as its replicas
+    /// share a checkout and must not update it at the same time
+    fn update_lead(&self, i: usize) -> usize {
+        let base_name = &self.processes[i].base_name;
+        self.processes
+            .iter()
+            .position(|p| &p.base_name == base_name)
+            .unwrap_or(i)
+    }
+
+    fn update(&mut self, i: usize) {
+        let lead = self.update_lead(i);
+        self.processes[lead].update();
+    }
+
+    fn is_updating(&self, base_name: &str) -> bool {
+        self.processes
+            .iter()
+            .any(|p| p.base_name == base_name && p.is_updating())
+    }
+
+    /// Stops and restarts the other replicas along with the one updating
+    fn apply_update_events(&mut self) {
+        for i in 0..self.processes.len() {
+            for event in self.processes[i].take_update_events() {
+                let base_name = self.processes[i].base_name.clone();
+                let outcome = self.processes[i].last_update.clone();
+
+                for (j, p) in self.processes.iter_mut().enumerate() {
+                    if j == i || p.base_name != base_name {
+                        continue;
+                    }
+
+                    match event {
+                        UpdateEvent::Started => p.pause_for_update(),
+                        UpdateEvent::Finished { succeeded } => {
+                            p.last_update = outcome.clone();
+                            p.resume_from_update(succeeded);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Polls every process (so background work progresses in any view) and
+    /// starts queued updates while staying within the concurrency limit
+    fn tick(&mut self) {
+        for p in &mut self.processes {
+            p.status();
+            p.poll_schedule();
+        }
+
+        self.poll_retired();
+        self.apply_update_events();
+        self.resolve_dependencies();
+        self.sample_usage();
+
+        let limit = self.config.update_concurrency.max(1);
+
+        while self.processes.iter().filter(|p| p.is_updating()).count() < limit {
+            let Some(base_name) = self.update_queue.pop_front() else {
+                break;
+            };
+
+            if let Some(i) = self.processes.iter().position(|p| p.base_name == base_name) {
+                self.update(i);
+            }
+        }
+    }
+
+    /// Samples CPU and memory of every running process tree, at most once per
+    /// USAGE_INTERVAL as it reads all of /proc
+    fn sample_usage(&mut self) {
+        if self.last_usage_sample.is_some_and(|t| t.elapsed() < USAGE_INTERVAL) {
+            return;
+        }
+        self.last_usage_sample = Some(Instant::now());
+
+        let table = ProcTable::read();
+        for p in &mut self.processes {
+            let pid = p.pid();
+            p.usage.sample(pid, &table);
+            p.check_limits();
+        }
+    }
+
+    /// Starts waiting processes whose dependencies are ready, starting idle
+    /// dependencies along the way. Dependencies outside the current profile
+    /// are ignored.
+    fn resolve_dependencies(&mut self) {
+        for i in 0..self.processes.len() {
+            if !self.processes[i].is_waiting() {
+                continue;
+            }
+
+            let mut ready = true;
+            let mut failed = None;
+            let mut to_start = vec![];
+
+            for dep in &self.processes[i].depends_on {
+                for (j, p) in self.processes.iter().enumerate() {
+                    if &p.base_name != dep {
+                        continue;
+                    }
+
+                    match p.readiness() {
+                        Readiness::Ready => {}
+                        // Started once for this wait and idle again, so it
+                        // exited, failed to prepare or was stopped by hand
+                        Readiness::Pending
+                            if p.is_idle() && self.processes[i].started_dependency(&p.name) =>
+                        {
+                            failed = Some(dep.clone())
+                        }
+                        Readiness::Pending => {
+                            ready = false;
+                            if p.is_idle() {
+                                to_start.push(j);
+                            }
+                        }
+                        Readiness::Failed => failed = Some(dep.clone()),
+                    }
+                }
+            }
+
+            for j in to_start {
+                self.processes[j].start();
+                let name = self.processes[j].name.clone();
+                self.processes[i].dependency_started(name);
+            }
+
+            if failed.is_some() || ready {
+                self.processes[i].dependencies_resolved(failed.as_deref());
+            }
+        }
+    }
+
+    fn clamp_selection(&mut self) {
+        let last = self.processes.len().saturating_sub(1);
+        self.state.select(Some(self.selected().min(last)));
+    }
+}
+
+fn log_line(p: &ManagedProcess, line: &str) -> Line<'static> {
+    if p.strip_ansi {
+        Line::from(ansi::strip(line))
+    } else {
+        ansi::parse_line(line)
+    }
+}
+
+/// E.g. "memory 2.0G, CPU 90% for 30s, then restart"
+fn describe_limits(p: &ManagedProcess) -> String {
+    let mut limits = vec![];
+    if let Some(max) = p.max_memory {
+        limits.push(format!("memory {}", format_bytes(max)));
+    }
+    if let Some(max) = p.max_cpu_percent {
+        limits.push(format!("CPU {max:.0}%"));
+    }
+
+    if limits.is_empty() {
+        return "-".into();
+    }
+
+    let action = match p.limit_action() {
+        LimitAction::Restart => "restart",
+        LimitAction::Warn => "warn",
+    };
+
+    format!(
+        "{} for {}, then {}",
+        limits.join(", "),
+        format_duration(p.limit_window),
+        action
+    )
+}
+
+fn instances(config: &ProcessConfig) -> Vec<ManagedProcess> {
+    (1..=config.instance_count())
+        .map(|i| ManagedProcess::new(config, i))
+        .collect()
 }
 
 struct TerminalGuard {
@@ -90,7 +745,7 @@ impl TerminalGuard {
     fn new() -> Result<Self, io::Error> {
         enable_raw_mode()?;
         let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen)?;
+        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
 
         let backend = CrosstermBackend::new(stdout);
         let terminal = Terminal::new(backend)?;
@@ -102,43 +757,201 @@ impl TerminalGuard {
 impl Drop for TerminalGuard {
     fn drop(&mut self) {
         disable_raw_mode().ok();
-        execute!(self.terminal.backend_mut(), LeaveAlternateScreen).ok();
+        execute!(self.terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture).ok();
     }
 }
 
-fn handle_key(app: &mut App, code: KeyCode) {
-    let keybinds = get_keybinds();
+/// Runs without the TUI, printing every process's output until Ctrl+C
+fn run_headless(mut app: App) {
+    let width = app.processes.iter().map(|p| p.name.chars().count()).max().unwrap_or(0);
+    for p in &app.processes {
+        headless::echo(p, width);
+    }
 
-    match app.view {
-        View::List => {
-            if let Some(bind) = keybinds.get(&code) {
-                match bind.t {
-                    KeybindType::Down => app.next(),
-                    KeybindType::Up => app.previous(),
-                    KeybindType::Restart => {
-                        let i = app.selected();
-                        app.processes[i].restart();
-                    }
-                    KeybindType::Stop => {
-                        let i = app.selected();
-                        app.processes[i].stop();
-                    }
-                    KeybindType::Start => {
-                        let i = app.selected();
-                        app.processes[i].start();
-                    }
-                    KeybindType::Enter => app.view = View::Logs,
-                    KeybindType::Quit => app.view = View::QuitConfirm,
-                    KeybindType::Update => {
-                        let i = app.selected();
-                        app.processes[i].update();
-                    }
-                    _ => {}
+    app.start_autostart();
+
+    while RUNNING.load(Ordering::Relaxed) {
+        app.tick();
+        thread::sleep(HEADLESS_TICK);
+    }
+
+    app.stop_all();
+}
+
+/// Bottom bar style hints, e.g. ` Quit <q> `, using the first key of each
+/// bind. Each hint is added to `buttons` assuming the line is drawn at `at`.
+fn keybind_hints(
+    binds: &[(Keybind, Vec<KeyChord>)],
+    at: Position,
+    buttons: &mut Vec<(Rect, KeybindType)>,
+) -> Vec<Span<'static>> {
+    let mut spans: Vec<Span> = vec![];
+    let mut x = at.x;
+
+    for (bind, keys) in binds {
+        let Some(key) = keys.first() else {
+            continue;
+        };
+
+        let name = Span::from(format!(" {} ", bind.name));
+        let key = format!("<{}>", key.display()).blue().bold();
+        let width = (name.width() + key.width()) as u16;
+
+        buttons.push((Rect::new(x, at.y, width, 1), bind.t));
+        x += width;
+
+        spans.push(name);
+        spans.push(key);
+    }
+
+    spans.push(" ".into());
+    spans
+}
+
+/// Runs an action on the selected process as the process list does
+fn run_list_action(app: &mut App, t: KeybindType) {
+    match t {
+        KeybindType::Down => app.next(),
+        KeybindType::Up => app.previous(),
+        KeybindType::Restart => {
+            if let Some(p) = app.selected_process() {
+                p.restart();
+            }
+        }
+        KeybindType::Stop => {
+            if let Some(p) = app.selected_process() {
+                p.stop();
+            }
+        }
+        KeybindType::Start => {
+            if let Some(p) = app.selected_process() {
+                p.start();
+            }
+        }
+        KeybindType::Enter if !app.processes.is_empty() => {
+            app.view = View::Logs
+        }
+        KeybindType::Details if !app.processes.is_empty() => {
+            app.detail_scroll = 0;
+            app.view = View::Detail
+        }
+        KeybindType::Quit => app.view = View::QuitConfirm,
+        KeybindType::Help => app.help = true,
+        KeybindType::Update if !app.processes.is_empty() => app.update(app.selected()),
+        KeybindType::UpdateAll => app.update_all(None),
+        KeybindType::UpdateGroup => {
+            let group = app.selected_process().and_then(|p| p.group.clone());
+            if let Some(group) = group {
+                app.update_all(Some(&group));
+            }
+        }
+        KeybindType::Profiles => app.open_profiles(),
+        KeybindType::ScaleUp => app.scale_up(),
+        KeybindType::ScaleDown => app.scale_down(),
+        KeybindType::UpdateProgress if !app.processes.is_empty() => {
+            app.view = View::Update
+        }
+        _ => {}
+    }
+}
+
+fn run_command(app: &mut App, command: Command) {
+    // Losing the history isn't worth interrupting the command for
+    record_history(&mut app.palette_history, &command.label).ok();
+
+    match command.action {
+        PaletteAction::Key(t, process) => {
+            if let Some(name) = process {
+                let Some(i) = app.processes.iter().position(|p| p.name == name) else {
+                    return;
+                };
+                app.state.select(Some(i));
+            }
+
+            run_list_action(app, t);
+        }
+        PaletteAction::Profile(profile) => app.switch_profile(profile),
+    }
+}
+
+fn handle_key(app: &mut App, key: KeyEvent) {
+    if app.attached {
+        let detach = app.keybinds.get(&[KeybindContext::Attached], None, &key).is_some();
+
+        match app.selected_process() {
+            Some(p) if p.accepts_input() && !detach => {
+                if let Some(bytes) = pty::key_bytes(&key) {
+                    p.send_input(&bytes);
                 }
+                return;
             }
+            _ => app.attached = false,
+        }
+
+        if detach {
+            return;
         }
+    }
+
+    if let Some(ref mut input) = app.input_prompt {
+        match key.code {
+            KeyCode::Esc => app.input_prompt = None,
+            KeyCode::Enter => {
+                let line = format!("{input}\n");
+                app.input_prompt = None;
+
+                if let Some(p) = app.selected_process() {
+                    push_line(&p.logs, format!("> {}", line.trim_end()));
+                    p.send_input(line.as_bytes());
+                }
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                input.push(c)
+            }
+            _ => {}
+        }
+        return;
+    }
 
-        View::QuitConfirm => match code {
+    if let Some(ref mut palette) = app.palette {
+        match key.code {
+            KeyCode::Esc => app.palette = None,
+            KeyCode::Enter => {
+                let command = palette.selected().cloned();
+                app.palette = None;
+
+                if let Some(command) = command {
+                    run_command(app, command);
+                }
+            }
+            KeyCode::Up => palette.previous(),
+            KeyCode::Down | KeyCode::Tab => palette.next(),
+            KeyCode::Backspace => palette.pop(),
+            KeyCode::Char(c) if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                palette.push(c)
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    let bind = app
+        .keybinds
+        .get(app.view.contexts(), app.view.actions(), &key)
+        .map(|x| x.t);
+
+    if app.help {
+        if matches!(bind, Some(KeybindType::Help | KeybindType::Escape)) || key.code == KeyCode::Esc {
+            app.help = false;
+        }
+        return;
+    }
+
+    if let View::QuitConfirm = app.view {
+        match key.code {
             KeyCode::Char('y') => {
                 RUNNING.store(false, Ordering::Relaxed);
             }
@@ -146,29 +959,202 @@ fn handle_key(app: &mut App, code: KeyCode) {
                 app.view = View::List;
             }
             _ => {}
+        }
+        return;
+    }
+
+    if let Some(t) = bind {
+        handle_action(app, t);
+    }
+}
+
+/// Runs an action in the current view, from either a key or a clicked hint
+fn handle_action(app: &mut App, t: KeybindType) {
+    match t {
+        KeybindType::Palette => {
+            app.open_palette();
+            return;
+        }
+        KeybindType::Help => {
+            app.help = true;
+            return;
+        }
+        _ => {}
+    }
+
+    match app.view {
+        View::List => run_list_action(app, t),
+
+        View::QuitConfirm => {}
+
+        View::Profiles => {
+            let names = app.profile_names();
+            let i = app.profile_state.selected().unwrap_or(0);
+
+            match t {
+                KeybindType::Escape => app.view = View::List,
+                KeybindType::Up => {
+                    app.profile_state
+                        .select(Some((i + names.len() - 1) % names.len()));
+                }
+                KeybindType::Down => {
+                    app.profile_state.select(Some((i + 1) % names.len()));
+                }
+                KeybindType::Enter => {
+                    app.switch_profile(names[i].clone());
+                    app.view = View::List;
+                }
+                _ => {}
+            }
+        }
+
+        View::Update => match t {
+            KeybindType::Escape => app.view = View::List,
+            KeybindType::Update if !app.processes.is_empty() => app.update(app.selected()),
+            _ => {}
         },
 
-        View::Logs => {
-            if let Some(bind) = keybinds.get(&code) {
-                match bind.t {
-                    KeybindType::Escape => app.view = View::List,
-                    KeybindType::Up => {
-                        if app.log_scroll > 0 {
-                            app.log_scroll -= 1;
-                        }
-                    }
-                    KeybindType::Down => app.log_scroll += 1,
-                    _ => {}
+        View::Detail => match t {
+            KeybindType::Escape => app.view = View::List,
+            KeybindType::Up => app.detail_scroll = app.detail_scroll.saturating_sub(1),
+            KeybindType::Down => app.detail_scroll += 1,
+            _ => {}
+        },
+
+        View::Logs => match t {
+            KeybindType::Escape => app.view = View::List,
+            KeybindType::Attach if app.selected_process().is_some_and(|p| p.accepts_input()) => {
+                app.attached = true
+            }
+            KeybindType::Detach => app.attached = false,
+            KeybindType::SendInput if app.selected_process().is_some_and(|p| p.accepts_input()) => {
+                app.input_prompt = Some(String::new())
+            }
+            KeybindType::Up => {
+                app.log_scroll = app.log_scroll.saturating_sub(1)
+            }
+            KeybindType::Down => app.log_scroll += 1,
+            KeybindType::Left if !app.log_wrap => {
+                app.log_hscroll = app.log_hscroll.saturating_sub(SCROLL_COLUMNS)
+            }
+            KeybindType::Right if !app.log_wrap => {
+                app.log_hscroll = app.log_hscroll.saturating_add(SCROLL_COLUMNS)
+            }
+            KeybindType::Wrap => app.log_wrap = !app.log_wrap,
+            _ => {}
+        },
+    }
+}
+
+fn handle_mouse(app: &mut App, mouse: MouseEvent) {
+    match mouse.kind {
+        MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+            let up = mouse.kind == MouseEventKind::ScrollUp;
+
+            if let Some(ref mut palette) = app.palette {
+                if up {
+                    palette.previous();
+                } else {
+                    palette.next();
                 }
+            } else if let View::Logs = app.view {
+                app.log_scroll = if up {
+                    app.log_scroll.saturating_sub(SCROLL_LINES)
+                } else {
+                    app.log_scroll.saturating_add(SCROLL_LINES)
+                };
+            } else if !app.help {
+                handle_action(app, if up { KeybindType::Up } else { KeybindType::Down });
             }
         }
+
+        MouseEventKind::ScrollLeft | MouseEventKind::ScrollRight => {
+            if let View::Logs = app.view
+                && app.palette.is_none()
+                && !app.help
+            {
+                handle_action(
+                    app,
+                    if mouse.kind == MouseEventKind::ScrollLeft {
+                        KeybindType::Left
+                    } else {
+                        KeybindType::Right
+                    },
+                );
+            }
+        }
+
+        MouseEventKind::Down(MouseButton::Left) => {
+            // Clicking anywhere dismisses overlays and the input prompt, so
+            // keys typed afterwards aren't sent to a process by surprise
+            if app.help || app.palette.is_some() || app.input_prompt.is_some() {
+                app.help = false;
+                app.palette = None;
+                app.input_prompt = None;
+                return;
+            }
+
+            let position = Position::new(mouse.column, mouse.row);
+            if let Some(&(_, t)) = app.buttons.iter().find(|x| x.0.contains(position)) {
+                // While attached the only way out is detaching
+                if !app.attached || t == KeybindType::Detach {
+                    handle_action(app, t);
+                }
+                return;
+            }
+
+            if app.attached {
+                return;
+            }
+
+            // Rows start below the border, and below the header for the table
+            let row = match app.view {
+                View::List => (mouse.row as usize).checked_sub(2).map(|x| x + app.state.offset()),
+                View::Profiles => {
+                    (mouse.row as usize).checked_sub(1).map(|x| x + app.profile_state.offset())
+                }
+                _ => None,
+            };
+            let count = match app.view {
+                View::Profiles => app.profile_names().len(),
+                _ => app.processes.len(),
+            };
+            let Some(row) = row.filter(|x| *x < count) else {
+                return;
+            };
+
+            let double = app
+                .last_click
+                .is_some_and(|(t, r)| r == row && t.elapsed() < DOUBLE_CLICK);
+            app.last_click = Some((Instant::now(), row));
+
+            match app.view {
+                View::Profiles => app.profile_state.select(Some(row)),
+                _ => app.state.select(Some(row)),
+            }
+
+            if double {
+                app.last_click = None;
+                handle_action(app, KeybindType::Enter);
+            }
+        }
+
+        _ => {}
     }
 }
 
 fn main() -> Result<(), io::Error> {
+    // ---- Parse arguments ----
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        exit(1);
+    });
+
     // ---- Ctrl+C handler ----
     ctrlc::set_handler(|| {
-        RUNNING.store(false, Ordering::Relaxed);
+        if !RUNNING.swap(false, Ordering::Relaxed) {
+            FORCE_EXIT.store(true, Ordering::Relaxed);
+        }
     })
         .expect("Failed to set Ctrl-C handler");
 
@@ -178,39 +1164,102 @@ fn main() -> Result<(), io::Error> {
         exit(1);
     });
 
-    // ---- Setup terminal (RAII safe) ----
-    let mut guard = TerminalGuard::new()?;
+    for name in args.start.iter().chain(&args.skip) {
+        if !config.processes.iter().any(|x| &x.name == name) {
+            eprintln!("Unknown process: {name}");
+            exit(1);
+        }
+    }
+
+    for p in &config.processes {
+        if let Some(ref expr) = p.schedule {
+            if p.kind != ProcessKind::Task {
+                eprintln!("Process {} has a schedule but is not a task", p.name);
+                exit(1);
+            }
+
+            if let Err(e) = Schedule::parse(expr) {
+                eprintln!("Process {}: {e}", p.name);
+                exit(1);
+            }
+        }
+
+        if let Some(ref max_memory) = p.max_memory
+            && let Err(e) = parse_bytes(max_memory)
+        {
+            eprintln!("Process {}: {e}", p.name);
+            exit(1);
+        }
+
+        for dep in &p.depends_on {
+            if dep == &p.name {
+                eprintln!("Process {} depends on itself", p.name);
+                exit(1);
+            }
+
+            if !config.processes.iter().any(|x| &x.name == dep) {
+                eprintln!("Process {} depends on unknown process: {dep}", p.name);
+                exit(1);
+            }
+        }
+    }
+
+    if let Some(cycle) = config.dependency_cycle() {
+        eprintln!("Dependency cycle: {}", cycle.join(" -> "));
+        exit(1);
+    }
+
+    for name in config.profiles.keys() {
+        if let Err(e) = config.resolve(Some(name)) {
+            eprintln!("{e}");
+            exit(1);
+        }
+    }
 
     // ---- Build app ----
-    let mut app = App::new(
-        config
-            .processes
-            .iter()
-            .map(|x| {
-                ManagedProcess::new(
-                    x
-                )
-            })
-            .collect(),
-    );
+    let mut app = App::new(config, args).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        exit(1);
+    });
 
-    // ---- Start all processes ----
-    for p in &mut app.processes {
-        p.start();
+    if app.args.no_tui {
+        run_headless(app);
+        return Ok(());
     }
 
+    // ---- Setup terminal (RAII safe) ----
+    let mut guard = TerminalGuard::new()?;
+
+    // ---- Start autostart processes ----
+    app.start_autostart();
+
     // ---- Main event loop ----
     while RUNNING.load(Ordering::Relaxed) {
+        app.tick();
+
         guard.terminal.draw(|f| {
             let size = f.area();
+            app.buttons.clear();
 
             match app.view {
                 View::List => {
-                    let items: Vec<ListItem> = app
+                    let name_width = app
+                        .processes
+                        .iter()
+                        .map(|p| p.name.chars().count())
+                        .max()
+                        .unwrap_or(0)
+                        .max(4) as u16;
+
+                    let rows: Vec<Row> = app
                         .processes
                         .iter_mut()
                         .map(|p| {
-                            let status = p.status();
+                            let mut status = p.status();
+                            if app.update_queue.contains(&p.base_name) {
+                                status = "Update queued".to_string();
+                            }
+
                             let exit_code = p
                                 .exit_status
                                 .and_then(|s| s.code())
@@ -222,41 +1271,128 @@ fn main() -> Result<(), io::Error> {
                                 .map(|t| format_duration(t.elapsed()))
                                 .unwrap_or_else(|| "0s".into());
 
-                            ListItem::new(format!(
-                                "{} [{}{} {}]",
-                                p.name, status, exit_code, runtime
-                            ))
+                            // Finished tasks already include the outcome and duration
+                            let details = if p.is_task() && p.child.is_none() {
+                                String::new()
+                            } else {
+                                format!("{} {}", exit_code, runtime)
+                            };
+
+                            let git = app
+                                .git
+                                .get(p.cwd.as_deref().unwrap_or("."))
+                                .map(|x| x.display())
+                                .unwrap_or_default();
+
+                            let note = match (&p.limit_warning, &p.last_update) {
+                                (Some(warning), _) => warning.clone().yellow(),
+                                (_, Some(UpdateOutcome::Failed(e))) => {
+                                    format!("Update failed ({e})").red()
+                                }
+                                (_, Some(UpdateOutcome::UpToDate)) => "Up to date".dark_gray(),
+                                _ => "".into(),
+                            };
+
+                            let next_run = p
+                                .next_run
+                                .map(|t| format_next_run(t, Local::now()))
+                                .unwrap_or_default();
+
+                            let usage = p.usage.current().filter(|_| p.child.is_some());
+                            let (cpu, memory, threads) = match usage {
+                                Some(u) => (
+                                    format!("{:.0}%", u.cpu_percent),
+                                    format_bytes(u.rss_bytes),
+                                    u.threads.to_string(),
+                                ),
+                                None => Default::default(),
+                            };
+
+                            Row::new(vec![
+                                Cell::from(p.name.clone()),
+                                Cell::from(format!("[{}{}]", status, details)),
+                                Cell::from(Line::from(cpu).right_aligned()),
+                                Cell::from(Line::from(memory).right_aligned()),
+                                Cell::from(Line::from(threads).right_aligned()),
+                                Cell::from(git.cyan()),
+                                Cell::from(next_run),
+                                Cell::from(note),
+                            ])
                         })
                         .collect();
 
-                    let mut binds = get_keybinds()
-                        .iter()
-                        .filter(|x| x.1.context == KeybindContext::Main)
-                        .map(|x| (x.0.clone(), x.1.clone()))
-                        .collect::<Vec<(KeyCode, Keybind)>>();
+                    // Help first so it stays visible when the bar is cut off
+                    let mut binds = app.view_keybinds();
+                    binds.sort_by_key(|x| (x.0.t != KeybindType::Help, x.0.name.clone()));
 
-                    binds.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+                    // Titles are drawn just inside the corner of the border
+                    let at = Position::new(size.x + 1, size.bottom().saturating_sub(1));
+                    let instructions = Line::from(keybind_hints(&binds, at, &mut app.buttons));
 
-                    let mut spans: Vec<Span> = vec![];
+                    let title = match app.profile {
+                        Some(ref profile) => format!("Processes ({profile})"),
+                        None => "Processes".to_string(),
+                    };
 
-                    for bind in binds {
-                        spans.push(format!(" {} ", bind.1.name).into());
-                        spans.push(
-                            format!("<{}>", keycode_display(&bind.0))
-                                .blue()
-                                .bold(),
-                        );
-                    }
+                    let header = Row::new(vec![
+                        Cell::from("Name"),
+                        Cell::from("Status"),
+                        Cell::from(Line::from("CPU").right_aligned()),
+                        Cell::from(Line::from("Mem").right_aligned()),
+                        Cell::from(Line::from("Thr").right_aligned()),
+                        Cell::from("Git"),
+                        Cell::from("Next Run"),
+                        Cell::from(""),
+                    ])
+                    .bold();
 
-                    spans.push(" ".into());
+                    let table = Table::new(
+                        rows,
+                        [
+                            Constraint::Length(name_width),
+                            Constraint::Length(32),
+                            Constraint::Length(5),
+                            Constraint::Length(6),
+                            Constraint::Length(4),
+                            Constraint::Length(24),
+                            Constraint::Length(10),
+                            Constraint::Fill(1),
+                        ],
+                    )
+                    .header(header)
+                    .block(
+                        Block::default()
+                            .title(title)
+                            .title_bottom(instructions)
+                            .borders(Borders::ALL),
+                    )
+                    .row_highlight_style(
+                        Style::default().add_modifier(Modifier::REVERSED),
+                    )
+                    .highlight_symbol(">> ");
 
-                    let instructions = Line::from(spans);
+                    f.render_stateful_widget(table, size, &mut app.state);
+                }
+
+                View::Profiles => {
+                    let items: Vec<ListItem> = app
+                        .profile_names()
+                        .into_iter()
+                        .map(|name| {
+                            let marker = if name == app.profile { "*" } else { " " };
+                            let label = name.unwrap_or_else(|| "(all processes)".into());
+                            ListItem::new(format!("{marker} {label}"))
+                        })
+                        .collect();
+
+                    let at = Position::new(size.x + 1, size.bottom().saturating_sub(1));
+                    let hints = Line::from(keybind_hints(&app.view_keybinds(), at, &mut app.buttons));
 
                     let list = List::new(items)
                         .block(
                             Block::default()
-                                .title("Processes")
-                                .title_bottom(instructions.centered())
+                                .title("Profiles")
+                                .title_bottom(hints)
                                 .borders(Borders::ALL),
                         )
                         .highlight_style(
@@ -264,34 +1400,247 @@ fn main() -> Result<(), io::Error> {
                         )
                         .highlight_symbol(">> ");
 
-                    f.render_stateful_widget(list, size, &mut app.state);
+                    f.render_stateful_widget(list, size, &mut app.profile_state);
+                }
+
+                View::Update => {
+                    let binds = app.view_keybinds();
+                    let selected = app.update_lead(app.selected());
+                    let p = &mut app.processes[selected];
+                    let status = p.status();
+                    let steps = p.update_steps.lock().unwrap().clone();
+
+                    let items: Vec<ListItem> = steps
+                        .iter()
+                        .map(|step| {
+                            let line = match step.state {
+                                StepState::Pending => Line::from(format!("[ ] {}", step.name)),
+                                StepState::Running(t) => Line::from(format!(
+                                    "[~] {} ({})",
+                                    step.name,
+                                    format_duration(t.elapsed())
+                                ))
+                                .yellow(),
+                                StepState::Succeeded(d) => Line::from(format!(
+                                    "[✓] {} ({})",
+                                    step.name,
+                                    format_duration(d)
+                                ))
+                                .green(),
+                                StepState::Failed(d, ref e) => Line::from(format!(
+                                    "[✗] {} ({}): {}",
+                                    step.name,
+                                    format_duration(d),
+                                    e
+                                ))
+                                .red(),
+                                StepState::Skipped => {
+                                    Line::from(format!("[-] {} (skipped)", step.name)).dark_gray()
+                                }
+                            };
+                            ListItem::new(line)
+                        })
+                        .collect();
+
+                    let layout = Layout::vertical([
+                        Constraint::Length(steps.len() as u16 + 2),
+                        Constraint::Min(0),
+                    ])
+                    .split(size);
+
+                    let at = Position::new(layout[0].x + 1, layout[0].bottom().saturating_sub(1));
+                    let hints = Line::from(keybind_hints(&binds, at, &mut app.buttons));
+
+                    let list = List::new(items).block(
+                        Block::default()
+                            .title(format!("Update: {} [{}]", p.name, status))
+                            .title_bottom(hints)
+                            .borders(Borders::ALL),
+                    );
+
+                    let text: Vec<Line> = {
+                        let logs = p.logs.lock().unwrap();
+                        let height = layout[1].height.saturating_sub(2) as usize;
+                        logs.iter()
+                            .skip(logs.len().saturating_sub(height))
+                            .map(|line| log_line(p, line))
+                            .collect()
+                    };
+
+                    let output = Paragraph::new(text)
+                        .block(Block::default().title("Output").borders(Borders::ALL));
+
+                    f.render_widget(list, layout[0]);
+                    f.render_widget(output, layout[1]);
                 }
 
                 View::Logs => {
                     let selected = app.selected();
+                    let mut title = vec![Span::from(format!("Logs: {}", app.processes[selected].name))];
 
-                    let text = {
-                        let logs = app.processes[selected]
-                            .logs
-                            .lock()
-                            .unwrap();
+                    let binds = if app.attached {
+                        title.push(" ".into());
+                        title.push(" ATTACHED ".reversed().bold());
+                        app.keybinds.for_contexts(&[KeybindContext::Attached])
+                    } else {
+                        app.view_keybinds()
+                    };
 
-                        logs.iter().fold(String::new(), |mut acc, line| {
-                            acc.push_str(line);
-                            acc.push('\n');
-                            acc
-                        })
+                    let width: usize = title.iter().map(|x| x.width()).sum();
+                    let at = Position::new(size.x + 1 + width as u16, size.y);
+                    title.extend(keybind_hints(&binds, at, &mut app.buttons));
+
+                    let (logs_area, prompt_area) = match app.input_prompt {
+                        Some(_) => {
+                            let [logs, prompt] =
+                                Layout::vertical([Constraint::Fill(1), Constraint::Length(3)]).areas(size);
+                            (logs, Some(prompt))
+                        }
+                        None => (size, None),
+                    };
+
+                    // Lines above the scroll position are skipped rather than
+                    // scrolled past, as their height depends on wrapping
+                    let text: Vec<Line> = {
+                        let p = &app.processes[selected];
+                        let logs = p.logs.lock().unwrap();
+                        app.log_scroll = app.log_scroll.min(logs.len().saturating_sub(1) as u16);
+
+                        logs.iter()
+                            .skip(app.log_scroll as usize)
+                            .map(|line| log_line(p, line))
+                            .collect()
+                    };
+
+                    let block = Block::default()
+                        .title(Line::from(title))
+                        .borders(Borders::ALL);
+
+                    let paragraph = if app.log_wrap {
+                        Paragraph::new(text).block(block).wrap(Wrap { trim: false })
+                    } else {
+                        // Stop once the longest visible line is in view
+                        let inner = block.inner(logs_area);
+                        let longest = text
+                            .iter()
+                            .take(inner.height as usize)
+                            .map(|x| x.width())
+                            .max()
+                            .unwrap_or(0);
+                        let max_scroll = longest.saturating_sub(inner.width as usize);
+                        app.log_hscroll = (app.log_hscroll as usize).min(max_scroll) as u16;
+
+                        Paragraph::new(text).block(block).scroll((0, app.log_hscroll))
                     };
 
-                    let paragraph = Paragraph::new(text)
+                    f.render_widget(paragraph, logs_area);
+
+                    if let (Some(input), Some(area)) = (&app.input_prompt, prompt_area) {
+                        let prompt = Paragraph::new(format!("> {input}▏")).block(
+                            Block::default()
+                                .title("Send input (Enter to send, Esc to cancel)")
+                                .borders(Borders::ALL),
+                        );
+                        f.render_widget(prompt, area);
+                    }
+                }
+
+                View::Detail => {
+                    let selected = app.selected();
+                    let binds = app.view_keybinds();
+                    let p = &mut app.processes[selected];
+                    let status = p.status();
+
+                    let field = |name: &str, value: String| {
+                        Line::from(vec![format!("{name:<12}").bold(), value.into()])
+                    };
+                    let or_none = |x: Option<String>| x.unwrap_or_else(|| "-".into());
+
+                    let mut lines = vec![
+                        field("Status", status),
+                        field("PID", or_none(p.pid().map(|x| x.to_string()))),
+                        field("Command", p.command.join(" ")),
+                        field("Cwd", p.resolved_cwd()),
+                        field("Port", or_none(p.port.map(|x| x.to_string()))),
+                        field(
+                            "Started",
+                            or_none(p.start_time.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())),
+                        ),
+                        field("Uptime", or_none(p.started_at.map(|t| format_duration(t.elapsed())))),
+                        field("Restarts", p.restarts.to_string()),
+                        field("Limits", describe_limits(p)),
+                        Line::default(),
+                        "Exit history".bold().into(),
+                    ];
+
+                    if p.exits.is_empty() {
+                        lines.push("  No exits yet".dark_gray().into());
+                    }
+
+                    for exit in &p.exits {
+                        let reason = exit.reason.as_ref().map(|x| format!(" ({x})")).unwrap_or_default();
+                        let line = Line::from(format!(
+                            "  {}  {:<10} after {}{}",
+                            exit.at.format("%Y-%m-%d %H:%M:%S"),
+                            format_exit(&exit.status),
+                            or_none(exit.runtime.map(format_duration)),
+                            reason,
+                        ));
+                        lines.push(if exit.status.success() { line } else { line.red() });
+                    }
+
+                    lines.push(Line::default());
+                    lines.push("Environment (* set in config)".bold().into());
+
+                    for (key, (value, configured)) in p.effective_env() {
+                        let marker = if configured { "*" } else { " " };
+                        let line = Line::from(format!("{marker} {key}={value}"));
+                        lines.push(if configured { line.cyan() } else { line });
+                    }
+
+                    let [text, graphs] =
+                        Layout::vertical([Constraint::Fill(1), Constraint::Length(6)]).areas(size);
+                    let [cpu, memory] =
+                        Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(graphs);
+
+                    let at = Position::new(text.x + 1, text.bottom().saturating_sub(1));
+                    let hints = Line::from(keybind_hints(&binds, at, &mut app.buttons));
+
+                    let paragraph = Paragraph::new(lines)
                         .block(
                             Block::default()
-                                .title("Logs (ESC)")
+                                .title(format!("Details: {}", p.name))
+                                .title_bottom(hints)
                                 .borders(Borders::ALL),
                         )
-                        .scroll((app.log_scroll, 0));
+                        .scroll((app.detail_scroll, 0));
 
-                    f.render_widget(paragraph, size);
+                    let current = p.usage.current().filter(|_| p.child.is_some()).unwrap_or_default();
+                    let cpu_data: Vec<u64> =
+                        p.usage.history.iter().map(|u| u.cpu_percent.round() as u64).collect();
+                    let memory_data: Vec<u64> = p.usage.history.iter().map(|u| u.rss_bytes).collect();
+
+                    let cpu_graph = Sparkline::default()
+                        .block(
+                            Block::default()
+                                .title(format!("CPU {:.0}% ({} threads)", current.cpu_percent, current.threads))
+                                .borders(Borders::ALL),
+                        )
+                        .data(&cpu_data)
+                        .max(cpu_data.iter().copied().max().unwrap_or(0).max(100))
+                        .green();
+                    let memory_graph = Sparkline::default()
+                        .block(
+                            Block::default()
+                                .title(format!("Memory {}", format_bytes(current.rss_bytes)))
+                                .borders(Borders::ALL),
+                        )
+                        .data(&memory_data)
+                        .magenta();
+
+                    f.render_widget(paragraph, text);
+                    f.render_widget(cpu_graph, cpu);
+                    f.render_widget(memory_graph, memory);
                 }
 
                 View::QuitConfirm => {
@@ -308,25 +1657,171 @@ fn main() -> Result<(), io::Error> {
                     f.render_widget(prompt, size);
                 }
             }
+
+            if app.help {
+                let binds = app.view_keybinds();
+
+                let rows: Vec<Row> = binds
+                    .iter()
+                    .map(|(bind, keys)| {
+                        let keys = keys
+                            .iter()
+                            .map(|x| x.display())
+                            .collect::<Vec<_>>()
+                            .join(" / ");
+
+                        Row::new(vec![
+                            Cell::from(keys.blue().bold()),
+                            Cell::from(bind.name.clone()),
+                            Cell::from(bind.description.clone()),
+                        ])
+                    })
+                    .collect();
+
+                let [area] = Layout::horizontal([Constraint::Length(90)])
+                    .flex(Flex::Center)
+                    .areas(size);
+                let [area] = Layout::vertical([Constraint::Length(rows.len() as u16 + 2)])
+                    .flex(Flex::Center)
+                    .areas(area);
+
+                let table = Table::new(
+                    rows,
+                    [Constraint::Length(16), Constraint::Length(16), Constraint::Fill(1)],
+                )
+                .block(Block::default().title("Help").borders(Borders::ALL));
+
+                f.render_widget(Clear, area);
+                f.render_widget(table, area);
+            }
+
+            if let Some(ref mut palette) = app.palette {
+                let items: Vec<ListItem> = palette
+                    .matches()
+                    .map(|c| ListItem::new(c.label.clone()))
+                    .collect();
+
+                let [area] = Layout::horizontal([Constraint::Length(70)])
+                    .flex(Flex::Center)
+                    .areas(size);
+                let [_, area] = Layout::vertical([Constraint::Length(2), Constraint::Length(14)])
+                    .areas(area);
+                let [input, results] =
+                    Layout::vertical([Constraint::Length(2), Constraint::Fill(1)]).areas(area);
+
+                let prompt = Paragraph::new(format!("> {}▏", palette.input)).block(
+                    Block::default()
+                        .title("Commands")
+                        .borders(Borders::TOP | Borders::LEFT | Borders::RIGHT),
+                );
+
+                let list = List::new(items)
+                    .block(
+                        Block::default()
+                            .title_bottom(Line::from(" Enter to run, Esc to close ").right_aligned())
+                            .borders(Borders::LEFT | Borders::RIGHT | Borders::BOTTOM),
+                    )
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                    .highlight_symbol(">> ");
+
+                f.render_widget(Clear, area);
+                f.render_widget(prompt, input);
+                f.render_stateful_widget(list, results, &mut palette.state);
+            }
         })?;
 
         if event::poll(Duration::from_millis(500))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    handle_key(&mut app, key.code);
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => handle_key(&mut app, key),
+                Event::Mouse(mouse) => handle_mouse(&mut app, mouse),
+                Event::Resize(cols, rows) => {
+                    for p in &app.processes {
+                        p.resize_pty(pty::log_view_size_for(cols, rows));
+                    }
                 }
+                _ => {}
             }
         }
     }
 
     // ---- Clean shutdown (processes first) ----
-    for p in &mut app.processes {
+    for p in &app.processes {
         println!("{}", p.name);
-        p.stop();
     }
 
-    // Terminal restored automatically via Drop
-    println!();
+    // Restored before stopping, so Ctrl+C can skip the stop hooks
+    drop(guard);
+    app.stop_all();
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> App {
+        let config = toml::from_str(
+            r#"
+            [[processes]]
+            name = "a"
+            cmd = ["true"]
+            autostart = false
+
+            [[processes]]
+            name = "b"
+            cmd = ["true"]
+            autostart = false
+            "#,
+        )
+        .unwrap();
+
+        App::new(config, Args::default()).unwrap()
+    }
+
+    fn click(app: &mut App, column: u16, row: u16) {
+        handle_mouse(
+            app,
+            MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            },
+        );
+    }
+
+    #[test]
+    fn click_closes_the_input_prompt() {
+        let mut app = app();
+        app.view = View::Logs;
+        app.input_prompt = Some("secret".into());
+        app.buttons = vec![(Rect::new(0, 0, 4, 1), KeybindType::Escape)];
+
+        click(&mut app, 1, 0);
+        assert!(app.input_prompt.is_none());
+        assert!(matches!(app.view, View::Logs));
+
+        click(&mut app, 1, 0);
+        assert!(matches!(app.view, View::List));
+    }
+
+    #[test]
+    fn only_detach_is_clickable_while_attached() {
+        let mut app = app();
+        app.view = View::Logs;
+        app.attached = true;
+        app.buttons = vec![
+            (Rect::new(0, 0, 4, 1), KeybindType::Escape),
+            (Rect::new(5, 0, 6, 1), KeybindType::Detach),
+        ];
+
+        click(&mut app, 1, 0);
+        click(&mut app, 1, 3);
+        assert!(app.attached);
+        assert!(matches!(app.view, View::Logs));
+
+        click(&mut app, 6, 0);
+        assert!(!app.attached);
+    }
+}
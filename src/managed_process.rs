@@ -1,82 +1,601 @@
+use chrono::{DateTime, Local};
 use std::{
-    collections::VecDeque,
-    io::{BufRead, BufReader},
-    process::{Child, Command, ExitStatus, Stdio},
-    sync::{Arc, Mutex},
-    thread,
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{self, Write},
+    os::unix::process::CommandExt,
+    path::Path,
+    process::{Child, ChildStdin, Command, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
-use std::sync::atomic::{AtomicBool, Ordering};
-use crate::config::ProcessConfig;
+use nix::{
+    sys::signal::{killpg, Signal},
+    unistd::Pid,
+};
+
+use crate::build;
+use crate::config::{
+    parse_bytes, Hooks, LimitAction, Overlap, ProcessConfig, ProcessKind, UpdateCmd, UpdateIf,
+};
+use crate::logs::{self, LogBuffer, Logs};
+use crate::pty;
+use crate::resources::UsageTracker;
+use crate::schedule::Schedule;
+use crate::git;
+use crate::update::{self, StepProgress, StepState};
+use crate::util::{format_bytes, format_duration, format_exit};
+use crate::watch::FileWatcher;
 
-const MAX_LOG_LINES: usize = 2000;
 const GRACEFUL_TIMEOUT: Duration = Duration::from_millis(1000);
+const READER_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+const DEFAULT_DEBOUNCE_MS: u64 = 200;
+const MAX_EXIT_HISTORY: usize = 20;
+const DEFAULT_LIMIT_WINDOW_SECS: u64 = 30;
+const DEFAULT_STOP_HOOK_TIMEOUT_SECS: u64 = 30;
+
+/// Set on a forced exit, killing the stop hooks that are still running
+static CANCEL_STOP_HOOKS: AtomicBool = AtomicBool::new(false);
+
+/// Preparations in progress by base process, so replicas starting together
+/// run the pre-start hook and build once and share the result. They run with
+/// the env shared by every replica and their output goes to each replica
+/// waiting on them.
+static PREPARATIONS: Mutex<BTreeMap<String, Weak<Mutex<Job>>>> = Mutex::new(BTreeMap::new());
 
 pub struct ManagedProcess {
     pub name: String,
+    pub base_name: String,
+    pub instance: u32,
     pub command: Vec<String>,
     pub cwd: Option<String>,
     pub port: Option<u16>,
-    pub update_command: Option<Vec<String>>,
+    pub update_command: Option<UpdateCmd>,
+    pub autostart: bool,
+    pub env: HashMap<String, String>,
+    /// Env of the pre-start hook and build, see `ProcessConfig::shared_env`
+    preparation_env: HashMap<String, String>,
+    pub update_keep_running: bool,
+    pub update_if: Option<UpdateIf>,
+    pub group: Option<String>,
+    pub kind: ProcessKind,
+    pub depends_on: Vec<String>,
+    pub schedule: Option<Schedule>,
+    pub overlap: Overlap,
+    pub hooks: Hooks,
+    pub build_command: Option<Vec<String>>,
+    pub build_inputs: Vec<String>,
+    pub debounce: Duration,
+    pub max_memory: Option<u64>,
+    pub max_cpu_percent: Option<f64>,
+    pub limit_window: Duration,
+    pub on_limit: LimitAction,
+    pub strip_ansi: bool,
+    pub pty: bool,
 
     pub child: Option<Child>,
-    pub logs: Arc<Mutex<Vec<String>>>,
+    pub logs: Logs,
     pub started_at: Option<Instant>,
     pub exit_status: Option<ExitStatus>,
+    pub last_duration: Option<Duration>,
+    pub next_run: Option<DateTime<Local>>,
+    pub last_update: Option<UpdateOutcome>,
+    /// Wall clock time of the latest start, kept after the process exits
+    pub start_time: Option<DateTime<Local>>,
+    pub restarts: u32,
+    /// Past exits, most recent first
+    pub exits: Vec<ExitRecord>,
+    pub usage: UsageTracker,
+    /// Set while usage is above a limit with `on_limit = "warn"`
+    pub limit_warning: Option<String>,
+
+    special_status: Arc<Mutex<Option<String>>>,
+    has_started: bool,
+    start_pending: bool,
+    preparation: Arc<Mutex<Job>>,
+    /// The stop hook running in the background while stopping
+    stop_job: Option<(StopHook, Arc<Mutex<Job>>)>,
+    /// Set when started before the stop hooks finished
+    start_after_stop: bool,
+    skip_build: bool,
+    updating: bool,
+    /// Whether the process was running when its update began, so a
+    /// successful update only starts it again in that case
+    resume_after_update: bool,
+    update_events: Vec<UpdateEvent>,
+    update_job: Arc<Mutex<Job>>,
+    pub update_steps: Arc<Mutex<Vec<StepProgress>>>,
+    update_check: Arc<Mutex<Option<Result<bool, String>>>>,
+    watcher: Option<FileWatcher>,
+    wants_running: bool,
+    waiting: bool,
+    /// Dependencies started for the current wait, so each is started once
+    started_dependencies: Vec<String>,
+    queued_run: bool,
+    memory_over_since: Option<Instant>,
+    cpu_over_since: Option<Instant>,
+    /// Why the process is being stopped, for the exit history
+    stop_reason: Option<String>,
+    /// Master side of the pseudo-terminal while running with `pty`
+    pty_master: Option<File>,
+    stdin: Option<ChildStdin>,
+}
+
+#[derive(PartialEq)]
+pub enum Readiness {
+    Ready,
+    Pending,
+    Failed,
+}
 
-    special_status: Option<String>,
-    needs_restart: Arc<AtomicBool>
+#[derive(Clone, PartialEq)]
+pub enum UpdateOutcome {
+    Succeeded,
+    UpToDate,
+    Failed(String),
+}
+
+/// An update's progress, for the app to apply to the other replicas
+#[derive(Clone, Copy)]
+pub enum UpdateEvent {
+    Started,
+    Finished { succeeded: bool },
+}
+
+pub struct ExitRecord {
+    pub at: DateTime<Local>,
+    pub status: ExitStatus,
+    pub runtime: Option<Duration>,
+    /// Set when stopped by us for a reason other than the user, e.g. a limit
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+enum StopHook {
+    PreStop,
+    PostStop,
+}
+
+/// Progress of work done on a background thread, e.g. the pre-start hook and
+/// build that run before spawning
+#[derive(Default)]
+struct Job {
+    phase: Option<String>,
+    result: Option<Result<(), String>>,
+    /// Output so far, for processes that start waiting on the job late
+    output: LogBuffer,
+    /// Logs of the processes waiting on the job, which get its output
+    followers: Vec<Logs>,
 }
 
 impl ManagedProcess {
-    pub fn new(config: &ProcessConfig) -> Self {
-        Self {
+    pub fn new(base: &ProcessConfig, instance: u32) -> Self {
+        let config = &base.instance(instance);
+
+        let mut process = Self {
             name: config.name.to_string(),
+            base_name: base.name.to_string(),
+            instance,
             command: config.cmd.clone(),
             cwd: config.cwd.clone(),
             port: config.port,
             update_command: config.update_cmd.clone(),
+            autostart: config.autostart,
+            env: config.env.clone(),
+            preparation_env: base.shared_env(),
+            update_keep_running: config.update_keep_running,
+            update_if: config.update_if,
+            group: config.group.clone(),
+            kind: config.kind,
+            depends_on: config.depends_on.clone(),
+            schedule: None,
+            overlap: config.overlap,
+            hooks: config.hooks.clone(),
+            build_command: config.build_cmd.clone(),
+            build_inputs: config.build_inputs.clone(),
+            debounce: Duration::from_millis(config.debounce_ms.unwrap_or(DEFAULT_DEBOUNCE_MS)),
+            max_memory: None,
+            max_cpu_percent: config.max_cpu_percent,
+            limit_window: Duration::from_secs(
+                config.limit_window_secs.unwrap_or(DEFAULT_LIMIT_WINDOW_SECS),
+            ),
+            on_limit: config.on_limit,
+            strip_ansi: config.strip_ansi,
+            pty: config.pty,
             child: None,
-            logs: Arc::new(Mutex::new(Vec::new())),
+            logs: Logs::default(),
             started_at: None,
             exit_status: None,
-            special_status: None,
-            needs_restart: Arc::new(AtomicBool::new(false)),
+            last_duration: None,
+            next_run: None,
+            start_time: None,
+            restarts: 0,
+            exits: vec![],
+            usage: UsageTracker::default(),
+            limit_warning: None,
+            special_status: Arc::new(Mutex::new(None)),
+            has_started: false,
+            start_pending: false,
+            preparation: Arc::new(Mutex::new(Job::default())),
+            stop_job: None,
+            start_after_stop: false,
+            skip_build: false,
+            updating: false,
+            resume_after_update: false,
+            update_events: vec![],
+            update_job: Arc::new(Mutex::new(Job::default())),
+            update_check: Arc::new(Mutex::new(None)),
+            update_steps: Arc::new(Mutex::new(
+                config
+                    .update_cmd
+                    .iter()
+                    .flat_map(|x| x.steps())
+                    .map(|x| StepProgress::pending(x.display_name()))
+                    .collect(),
+            )),
+            last_update: None,
+            watcher: None,
+            wants_running: false,
+            waiting: false,
+            started_dependencies: vec![],
+            queued_run: false,
+            memory_over_since: None,
+            cpu_over_since: None,
+            stop_reason: None,
+            pty_master: None,
+            stdin: None,
+        };
+
+        if let Some(ref expr) = config.schedule {
+            match Schedule::parse(expr) {
+                Ok(schedule) => {
+                    process.next_run = schedule.next_after(Local::now());
+                    process.schedule = Some(schedule);
+                }
+                Err(e) => process.push_log(e),
+            }
+        }
+
+        // Explicitly configured values win
+        if config.force_color {
+            for key in ["FORCE_COLOR", "CLICOLOR_FORCE"] {
+                process.env.entry(key.to_string()).or_insert_with(|| "1".to_string());
+            }
+        }
+
+        if let Some(ref max_memory) = config.max_memory {
+            match parse_bytes(max_memory) {
+                Ok(bytes) => process.max_memory = Some(bytes),
+                Err(e) => process.push_log(e),
+            }
+        }
+
+        if !config.watch.is_empty() {
+            match FileWatcher::new(config.cwd.as_deref(), &config.watch, &config.ignore) {
+                Ok(watcher) => process.watcher = Some(watcher),
+                Err(e) => process.push_log(e),
+            }
         }
+
+        process
     }
 
     pub fn start(&mut self) {
-        self.special_status = None;
-        if self.child.is_some() {
+        self.set_status(None);
+
+        // The child may still be running its pre-stop hook
+        if self.stop_job.is_some() {
+            self.start_after_stop = true;
+            return;
+        }
+
+        if self.child.is_some() || self.start_pending || self.waiting {
+            return;
+        }
+
+        if self.depends_on.is_empty() {
+            self.launch();
+            return;
+        }
+
+        // The app starts it once all dependencies are ready
+        self.waiting = true;
+        self.started_dependencies.clear();
+        self.set_status(Some(format!("Waiting for {}", self.depends_on.join(", "))));
+    }
+
+    /// Called once the dependencies of a waiting process are ready, or with
+    /// the name of the one that failed
+    pub fn dependencies_resolved(&mut self, failed: Option<&str>) {
+        if !self.waiting {
+            return;
+        }
+
+        self.waiting = false;
+
+        match failed {
+            Some(name) => {
+                self.push_log(format!("Not starting, dependency {} failed", name));
+                self.set_status(Some(format!("Dependency failed ({name})")));
+            }
+            None => {
+                self.set_status(None);
+                self.launch();
+            }
+        }
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+
+    pub fn dependency_started(&mut self, name: String) {
+        self.started_dependencies.push(name);
+    }
+
+    /// Whether the dependency was started for the current wait
+    pub fn started_dependency(&self, name: &str) -> bool {
+        self.started_dependencies.iter().any(|x| x == name)
+    }
+
+    /// Whether the stop hooks are still running
+    pub fn is_stopping(&self) -> bool {
+        self.stop_job.is_some()
+    }
+
+    pub fn is_task(&self) -> bool {
+        self.kind == ProcessKind::Task
+    }
+
+    /// Whether nothing is running or about to run for this process
+    pub fn is_idle(&self) -> bool {
+        self.child.is_none()
+            && !self.start_pending
+            && !self.waiting
+            && !self.updating
+            && self.stop_job.is_none()
+    }
+
+    /// Whether processes depending on this one can start: services once they
+    /// are running, tasks once they exited successfully
+    pub fn readiness(&self) -> Readiness {
+        match self.kind {
+            ProcessKind::Service if self.child.is_some() => Readiness::Ready,
+            ProcessKind::Service => Readiness::Pending,
+            ProcessKind::Task if !self.is_idle() => Readiness::Pending,
+            ProcessKind::Task => match self.exit_status {
+                Some(status) if status.success() => Readiness::Ready,
+                Some(_) => Readiness::Failed,
+                None => Readiness::Pending,
+            },
+        }
+    }
+
+    /// Starts the task when its schedule is due, applying the overlap policy if
+    /// the previous run is still going
+    pub fn poll_schedule(&mut self) {
+        if self.queued_run && self.is_idle() {
+            self.queued_run = false;
+            self.push_log("Starting queued scheduled run");
+            self.start();
+        }
+
+        let now = Local::now();
+        let Some(schedule) = self.schedule.as_ref() else {
+            return;
+        };
+        match self.next_run {
+            Some(next) if next <= now => {}
+            _ => return,
+        }
+
+        self.next_run = schedule.next_after(now);
+
+        if self.is_idle() {
+            self.push_log("Starting scheduled run");
+            self.start();
             return;
         }
 
+        match self.overlap {
+            Overlap::Skip => self.push_log("Skipping scheduled run, previous run still going"),
+            Overlap::Queue => {
+                self.queued_run = true;
+                self.push_log("Queueing scheduled run until the previous run finished");
+            }
+            Overlap::Kill => {
+                self.push_log("Stopping previous run for scheduled run");
+                self.restart();
+            }
+        }
+    }
+
+    fn launch(&mut self) {
         if self.command.is_empty() {
             self.push_log("Command is empty");
             return;
         }
 
+        self.has_started = true;
+        self.wants_running = true;
+
+        // An update that kept the process running has already built it
+        let build_command = if std::mem::take(&mut self.skip_build) {
+            None
+        } else {
+            self.build_command.clone()
+        };
+
+        if self.hooks.pre_start.is_none() && build_command.is_none() {
+            self.spawn_child();
+            return;
+        }
+
+        // Hooks and builds may take a while (e.g. migrations), so they run in
+        // the background and status() spawns the process once they succeeded.
+        // A fresh slot per attempt means a stopped attempt is simply ignored.
+        self.start_pending = true;
+
+        let mut preparations = PREPARATIONS.lock().unwrap();
+        let running = preparations
+            .get(&self.base_name)
+            .and_then(Weak::upgrade)
+            .filter(|job| job.lock().unwrap().result.is_none());
+
+        if let Some(job) = running {
+            self.push_log(format!(
+                "Waiting for another instance of {} to prepare",
+                self.base_name
+            ));
+            self.preparation = job;
+            self.follow_preparation();
+            return;
+        }
+
+        self.preparation = Arc::new(Mutex::new(Job::default()));
+        preparations.insert(self.base_name.clone(), Arc::downgrade(&self.preparation));
+        drop(preparations);
+        self.follow_preparation();
+
+        // Output goes to every follower, copied outside the job's lock so a
+        // slow follower doesn't hold up status()
+        let logs = Logs::default();
+        let job = self.preparation.clone();
+        logs.lock().unwrap().set_echo(move |line| {
+            let followers = {
+                let mut job = job.lock().unwrap();
+                job.output.push(line.to_string());
+                job.followers.clone()
+            };
+
+            for logs in followers {
+                push_line(&logs, line);
+            }
+        });
+
+        let preparation = self.preparation.clone();
+        let pre_start = self.hooks.pre_start.clone();
+        let build_inputs = self.build_inputs.clone();
+        let base_name = self.base_name.clone();
+        let cwd = self.cwd.clone();
+        let env = self.preparation_env.clone();
+
+        thread::spawn(move || {
+            let set_phase = |phase: &str| {
+                preparation.lock().unwrap().phase = Some(phase.to_string());
+            };
+
+            let mut result = Ok(());
+
+            if let Some(pre_start) = pre_start {
+                set_phase("Pre-start");
+                result = run_hook("pre_start", &pre_start, cwd.as_deref(), &env, &logs, None)
+                    .map_err(|e| format!("Pre-start failed ({e})"));
+            }
+
+            if result.is_ok()
+                && let Some(build_command) = build_command
+            {
+                set_phase("Building");
+                result = run_build(
+                    &base_name,
+                    &build_command,
+                    &build_inputs,
+                    cwd.as_deref(),
+                    &env,
+                    &logs,
+                )
+                .map_err(|e| format!("Build failed ({e})"));
+            }
+
+            preparation.lock().unwrap().result = Some(result);
+        });
+    }
+
+    /// Copies what the preparation logged so far and gets the rest as it's
+    /// logged
+    fn follow_preparation(&self) {
+        let output: Vec<String> = {
+            let mut job = self.preparation.lock().unwrap();
+            job.followers.push(self.logs.clone());
+            job.output.iter().cloned().collect()
+        };
+
+        for line in output {
+            push_line(&self.logs, line);
+        }
+    }
+
+    fn spawn_child(&mut self) {
+        self.start_pending = false;
+
         let mut cmd = Command::new(&self.command[0]);
         cmd.args(&self.command[1..]);
+        cmd.envs(&self.env);
 
         if let Some(ref cwd) = self.cwd {
             cmd.current_dir(cwd);
         }
 
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
+        let mut master = None;
+        if self.pty {
+            match pty::attach(&mut cmd, pty::log_view_size()) {
+                Ok(file) => master = Some(file),
+                Err(e) => {
+                    self.push_log(format!("Failed to open pty: {}", e));
+                    return;
+                }
+            }
+        } else {
+            cmd.stdin(Stdio::piped());
+            cmd.stdout(Stdio::piped());
+            cmd.stderr(Stdio::piped());
+        }
+
+        let spawned = cmd.spawn();
+        // Closes our copies of the pty's slave side, so reading the master
+        // ends once the process exits
+        drop(cmd);
 
-        match cmd.spawn() {
+        match spawned {
             Ok(mut child) => {
+                if self.start_time.is_some() {
+                    self.restarts += 1;
+                }
+
                 self.started_at = Some(Instant::now());
+                self.start_time = Some(Local::now());
                 self.exit_status = None;
 
                 self.push_log(format!("Started: {}", self.command.join(" ")));
 
-                self.spawn_reader(child.stdout.take());
-                self.spawn_reader(child.stderr.take());
+                match master {
+                    Some(master) => {
+                        spawn_reader(master.try_clone().ok(), &self.logs);
+                        self.pty_master = Some(master);
+                    }
+                    None => {
+                        self.stdin = child.stdin.take();
+                        spawn_reader(child.stdout.take(), &self.logs);
+                        spawn_reader(child.stderr.take(), &self.logs);
+                    }
+                }
 
                 self.child = Some(child);
+
+                if let Some(post_start) = self.hooks.post_start.clone() {
+                    let logs = self.logs.clone();
+                    let cwd = self.cwd.clone();
+                    let env = self.env.clone();
+
+                    thread::spawn(move || {
+                        let _ = run_hook("post_start", &post_start, cwd.as_deref(), &env, &logs, None);
+                    });
+                }
             }
             Err(e) => {
                 self.push_log(format!("Failed to start: {}", e));
@@ -85,91 +604,254 @@ impl ManagedProcess {
     }
 
     pub fn update(&mut self) {
-        let update_args = match self.update_command.clone() {
-            Some(args) if !args.is_empty() => args,
-            _ => {
-                self.push_log("No valid update command configured");
-                return;
+        if !self.has_update_command() {
+            self.push_log("No valid update command configured");
+            return;
+        }
+
+        if self.updating {
+            self.push_log("Update already in progress");
+            return;
+        }
+
+        if self.update_if != Some(UpdateIf::Git) {
+            self.run_update();
+            return;
+        }
+
+        // Check for new commits first so nothing is stopped when there is
+        // nothing to update
+        self.updating = true;
+        self.update_check = Arc::new(Mutex::new(None));
+        self.update_job = Arc::new(Mutex::new(Job {
+            phase: Some("Checking".to_string()),
+            ..Job::default()
+        }));
+
+        let update_check = self.update_check.clone();
+        let logs = self.logs.clone();
+        let cwd = self.cwd.clone();
+
+        thread::spawn(move || {
+            push_line(&logs, "Checking for upstream changes");
+            let result = git::has_upstream_changes(cwd.as_deref());
+            *update_check.lock().unwrap() = Some(result);
+        });
+    }
+
+    pub fn has_update_command(&self) -> bool {
+        match self.update_command {
+            Some(ref update) => {
+                let steps = update.steps();
+                !steps.is_empty() && steps.iter().all(|x| !x.cmd.is_empty())
             }
+            None => false,
+        }
+    }
+
+    pub fn is_updating(&self) -> bool {
+        self.updating
+    }
+
+    fn run_update(&mut self) {
+        let steps = match self.update_command {
+            Some(ref update) => update.steps(),
+            None => vec![],
         };
 
-        // Stop running process first
-        self.stop();
+        self.pause_for_update();
+        self.update_events.push(UpdateEvent::Started);
+        self.set_status(None);
+
+        let build_command = if self.update_keep_running {
+            self.build_command.clone()
+        } else {
+            None
+        };
+
+        let mut progress: Vec<StepProgress> = steps
+            .iter()
+            .map(|x| StepProgress::pending(x.display_name()))
+            .collect();
+        if build_command.is_some() {
+            progress.push(StepProgress::pending("build".to_string()));
+        }
 
-        self.special_status = Some("Updating".to_string());
+        self.updating = true;
+        self.update_job = Arc::new(Mutex::new(Job::default()));
+        self.update_steps = Arc::new(Mutex::new(progress));
 
+        let job = self.update_job.clone();
+        let update_steps = self.update_steps.clone();
+        let build_inputs = self.build_inputs.clone();
+        let base_name = self.base_name.clone();
         let logs = self.logs.clone();
         let cwd = self.cwd.clone();
-        let start_command = self.command.clone();
+        let env = self.env.clone();
         let name = self.name.clone();
 
+        thread::spawn(move || {
+            let set_phase = |phase: String| job.lock().unwrap().phase = Some(phase);
 
-        let needs_restart = self.needs_restart.clone();
+            push_line(&logs, format!("Updating {}", name));
 
-        thread::spawn(move || {
-            {
-                let mut guard = logs.lock().unwrap();
-                guard.push(format!("Updating {}", name));
-            }
+            let mut result = update::run_steps(
+                &steps,
+                cwd.as_deref(),
+                &env,
+                &logs,
+                &update_steps,
+                set_phase,
+            );
+
+            if let Some(build_command) = build_command {
+                let start = Instant::now();
+                let set_build_state = |state: StepState| {
+                    if let Some(step) = update_steps.lock().unwrap().last_mut() {
+                        step.state = state;
+                    }
+                };
+
+                if result.is_ok() {
+                    job.lock().unwrap().phase = Some("Building".to_string());
+                    set_build_state(StepState::Running(start));
 
-            let mut cmd = Command::new(&update_args[0]);
-            cmd.args(&update_args[1..]);
+                    result = run_build(
+                        &base_name,
+                        &build_command,
+                        &build_inputs,
+                        cwd.as_deref(),
+                        &env,
+                        &logs,
+                    )
+                    .map_err(|e| format!("build: {e}"));
 
-            if let Some(ref dir) = cwd {
-                cmd.current_dir(dir);
+                    set_build_state(match result {
+                        Ok(()) => StepState::Succeeded(start.elapsed()),
+                        Err(ref e) => StepState::Failed(start.elapsed(), e.clone()),
+                    });
+                } else {
+                    set_build_state(StepState::Skipped);
+                }
             }
 
-            cmd.stdout(Stdio::piped());
-            cmd.stderr(Stdio::piped());
+            match result {
+                Ok(()) => push_line(&logs, "Update succeeded"),
+                Err(ref e) => push_line(&logs, format!("Update failed ({e})")),
+            }
 
-            match cmd.spawn() {
-                Ok(mut child) => {
-                    // stdout
-                    if let Some(stdout) = child.stdout.take() {
-                        let logs = logs.clone();
-                        thread::spawn(move || {
-                            let reader = BufReader::new(stdout);
-                            for line in reader.lines().flatten() {
-                                logs.lock().unwrap().push(line);
-                            }
-                        });
-                    }
+            job.lock().unwrap().result = Some(result);
+        });
+    }
 
-                    // stderr
-                    if let Some(stderr) = child.stderr.take() {
-                        let logs = logs.clone();
-                        thread::spawn(move || {
-                            let reader = BufReader::new(stderr);
-                            for line in reader.lines().flatten() {
-                                logs.lock().unwrap().push(line);
-                            }
-                        });
-                    }
+    /// Either stops right away, or keeps serving until the update and build
+    /// went through and only then restarts
+    pub fn pause_for_update(&mut self) {
+        self.resume_after_update = self.child.is_some() || self.start_pending || self.waiting;
+
+        if !self.update_keep_running {
+            self.stop();
+        }
+    }
+
+    /// Restarts the process after an update if it was running before
+    pub fn resume_from_update(&mut self, succeeded: bool) {
+        if std::mem::take(&mut self.resume_after_update) && succeeded {
+            self.skip_build = self.update_keep_running;
+            self.restart();
+        }
+    }
 
-                    let status = child.wait();
+    /// Updates started or finished since the last call
+    pub fn take_update_events(&mut self) -> Vec<UpdateEvent> {
+        std::mem::take(&mut self.update_events)
+    }
 
-                    logs.lock().unwrap().push(format!(
-                        "Update finished with status: {:?}",
-                        status
-                    ));
+    pub fn stop(&mut self) {
+        self.wants_running = false;
+        self.start_after_stop = false;
 
-                    // Restart main process
-                    needs_restart.store(true, Ordering::SeqCst);
+        if self.waiting {
+            self.waiting = false;
+            self.set_status(None);
+        }
 
-                }
-                Err(e) => {
-                    logs.lock().unwrap().push(format!("Update failed: {}", e));
-                }
+        if self.start_pending {
+            self.start_pending = false;
+            self.set_status(None);
+        }
+
+        if self.stop_job.is_some() {
+            return;
+        }
+
+        // Like pre_start, the hook may take a while (e.g. draining
+        // connections), so it runs in the background and status() stops the
+        // process once it finished
+        if self.child.is_some()
+            && let Some(pre_stop) = self.hooks.pre_stop.clone()
+        {
+            self.run_stop_hook(StopHook::PreStop, pre_stop);
+            return;
+        }
+
+        self.terminate();
+    }
+
+    /// Kills the process straight away without its stop hooks, for a forced
+    /// exit. See `cancel_stop_hooks` for hooks that are already running.
+    pub fn kill(&mut self) {
+        self.wants_running = false;
+        self.start_after_stop = false;
+
+        if let Some(mut child) = self.child.take() {
+            child.kill().ok();
+            if let Ok(status) = child.wait() {
+                self.record_exit(status);
             }
+            self.push_log("Killed");
+            self.started_at = None;
+        }
+    }
+
+    fn run_stop_hook(&mut self, hook: StopHook, args: Vec<String>) {
+        let (name, phase) = match hook {
+            StopHook::PreStop => ("pre_stop", "Pre-stop"),
+            StopHook::PostStop => ("post_stop", "Post-stop"),
+        };
+
+        let job = Arc::new(Mutex::new(Job {
+            phase: Some(phase.to_string()),
+            ..Job::default()
+        }));
+
+        let result = job.clone();
+        let logs = self.logs.clone();
+        let cwd = self.cwd.clone();
+        let env = self.env.clone();
+        let timeout = Duration::from_secs(
+            self.hooks
+                .stop_hook_timeout_secs
+                .unwrap_or(DEFAULT_STOP_HOOK_TIMEOUT_SECS),
+        );
+
+        thread::spawn(move || {
+            let outcome = run_hook(name, &args, cwd.as_deref(), &env, &logs, Some(timeout));
+            result.lock().unwrap().result = Some(outcome);
         });
+
+        self.stop_job = Some((hook, job));
     }
 
-    pub fn stop(&mut self) {
+    /// Kills the process, then starts the post-stop hook
+    fn terminate(&mut self) {
+        let had_child = self.child.is_some();
+
         if let Some(mut child) = self.child.take() {
             let pid = child.id().to_string();
 
             // --- Graceful shutdown ---
-            self.special_status = Some("Killing".to_string());
+            self.set_status(Some("Killing".to_string()));
             let _ = Command::new("kill").args(["-15", &pid]).output();
 
             let start = Instant::now();
@@ -177,10 +859,9 @@ impl ManagedProcess {
             let mut success = false;
             while start.elapsed() < GRACEFUL_TIMEOUT {
                 if let Ok(Some(status)) = child.try_wait() {
-                    self.exit_status = Some(status);
-                    self.started_at = None;
+                    self.record_exit(status);
                     self.push_log("Stopped gracefully");
-                    self.special_status = Some("Killed Gracefully".to_string());
+                    self.set_status(Some("Killed Gracefully".to_string()));
                     success = true;
                     break;
                 }
@@ -190,8 +871,10 @@ impl ManagedProcess {
             // --- Force kill ---
             if !success {
                 let _ = Command::new("kill").args(["-9", &pid]).output();
-                let _ = child.wait();
-                self.special_status = Some("Force Killed".to_string());
+                if let Ok(status) = child.wait() {
+                    self.record_exit(status);
+                }
+                self.set_status(Some("Force Killed".to_string()));
 
                 self.push_log("Force killed");
             }
@@ -199,15 +882,19 @@ impl ManagedProcess {
 
         // Optional fallback: kill by port
         if let Some(port) = self.port {
-            self.special_status = Some("Killing By Port".to_string());
+            self.set_status(Some("Killing By Port".to_string()));
             if let Some(pid) = pid_from_port(port) {
                 let _ = Command::new("kill").args(["-9", &pid]).output();
                 self.push_log(format!("Killed PID {} on port {}", pid, port));
-                self.special_status = Some(format!("Killed {}", pid));
+                self.set_status(Some(format!("Killed {}", pid)));
             }
         }
 
         self.started_at = None;
+
+        if had_child && let Some(post_stop) = self.hooks.post_stop.clone() {
+            self.run_stop_hook(StopHook::PostStop, post_stop);
+        }
     }
 
     pub fn restart(&mut self) {
@@ -216,55 +903,464 @@ impl ManagedProcess {
     }
 
     pub fn status(&mut self) -> String {
-        if self.needs_restart.swap(false, Ordering::SeqCst) {
-            self.special_status = None;
-            self.start();
-            return "Restarting".to_string();
+        let update_check = self.update_check.lock().unwrap().take();
+        match update_check {
+            Some(Ok(true)) => {
+                self.updating = false;
+                self.run_update();
+            }
+            Some(Ok(false)) => {
+                self.updating = false;
+                self.last_update = Some(UpdateOutcome::UpToDate);
+                self.push_log("Already up to date");
+            }
+            Some(Err(e)) => {
+                self.updating = false;
+                self.push_log(format!("Update failed ({e})"));
+                self.last_update = Some(UpdateOutcome::Failed(e));
+            }
+            None => {}
+        }
+
+        let change = self
+            .watcher
+            .as_ref()
+            .and_then(|x| x.take_change(self.debounce));
+
+        // Only restart what is meant to be running, e.g. not after being
+        // stopped by hand, and not in the middle of a build or update
+        if let Some(path) = change
+            && !self.is_task()
+            && self.wants_running
+            && !self.start_pending
+            && !self.updating
+        {
+            self.push_log(format!("Changed: {}", path));
+            self.restart();
+        }
+
+        if self.updating {
+            let (phase, result) = {
+                let mut job = self.update_job.lock().unwrap();
+                (job.phase.clone(), job.result.take())
+            };
+
+            match result {
+                Some(Ok(())) => {
+                    self.updating = false;
+                    self.last_update = Some(UpdateOutcome::Succeeded);
+                    self.update_events.push(UpdateEvent::Finished { succeeded: true });
+                    self.resume_from_update(true);
+                }
+                Some(Err(e)) => {
+                    self.updating = false;
+                    self.update_events.push(UpdateEvent::Finished { succeeded: false });
+                    self.resume_from_update(false);
+                    self.last_update = Some(UpdateOutcome::Failed(e));
+                }
+                None => return phase.unwrap_or_else(|| "Updating".to_string()),
+            }
         }
 
-        if let Some(ref special) = self.special_status {
-            return special.clone();
+        if self.start_pending {
+            // Not taken, as replicas waiting on the same preparation read it too
+            let (phase, result) = {
+                let preparation = self.preparation.lock().unwrap();
+                (preparation.phase.clone(), preparation.result.clone())
+            };
+
+            match result {
+                Some(Ok(())) => {
+                    self.set_status(None);
+                    self.spawn_child();
+                }
+                Some(Err(e)) => {
+                    self.start_pending = false;
+                    self.set_status(Some(e));
+                }
+                None => return phase.unwrap_or_else(|| "Starting".to_string()),
+            }
+        }
+
+        if let Some((hook, ref job)) = self.stop_job {
+            let (phase, result) = {
+                let job = job.lock().unwrap();
+                (job.phase.clone(), job.result.clone())
+            };
+
+            // Hook failures are in the logs and don't keep the process running
+            match result {
+                Some(_) => {
+                    self.stop_job = None;
+                    if let StopHook::PreStop = hook {
+                        self.terminate();
+                    }
+
+                    if self.stop_job.is_none() && std::mem::take(&mut self.start_after_stop) {
+                        self.start();
+                    }
+                }
+                None => return phase.unwrap_or_else(|| "Stopping".to_string()),
+            }
+        }
+
+        if let Some(special) = self.special_status.lock().unwrap().clone() {
+            return special;
         }
 
         if let Some(child) = &mut self.child {
-            if let Ok(Some(status)) = child.try_wait() {
-                self.exit_status = Some(status);
-                self.child = None;
-                self.started_at = None;
-                return "Stopped".to_string();
+            match child.try_wait() {
+                Ok(Some(status)) => {
+                    self.child = None;
+                    self.record_exit(status);
+
+                    if self.is_task() {
+                        self.push_log(match status.success() {
+                            true => "Task succeeded".to_string(),
+                            false => format!("Task failed ({})", format_exit(&status)),
+                        });
+                    }
+                }
+                _ => return "Running".to_string(),
             }
-            return "Running".to_string();
         }
+
+        if self.is_task()
+            && let Some(status) = self.exit_status
+        {
+            let duration = self.last_duration.map(format_duration).unwrap_or_default();
+
+            return match status.success() {
+                true => format!("Succeeded in {}", duration),
+                false => format!("Failed ({}) after {}", format_exit(&status), duration),
+            };
+        }
+
+        if self.exit_status.is_some() {
+            return "Stopped".to_string();
+        }
+
+        if !self.has_started {
+            return "Idle".to_string();
+        }
+
         "Stopped".to_string()
     }
 
-    pub fn logs(&self) -> Vec<String> {
-        self.logs
-            .lock()
-            .unwrap()
-            .iter()
-            .cloned()
-            .collect()
-    }
-
-    fn spawn_reader(&self, stream: Option<impl std::io::Read + Send + 'static>) {
-        if let Some(stream) = stream {
-            let logs = self.logs.clone();
-            thread::spawn(move || {
-                let reader = BufReader::new(stream);
-                for line in reader.lines().flatten() {
-                    let mut guard = logs.lock().unwrap();
-                    guard.push(line);
+    fn record_exit(&mut self, status: ExitStatus) {
+        self.pty_master = None;
+        self.stdin = None;
+        self.exit_status = Some(status);
+        self.last_duration = self.started_at.take().map(|t| t.elapsed());
+
+        self.exits.insert(
+            0,
+            ExitRecord {
+                at: Local::now(),
+                status,
+                runtime: self.last_duration,
+                reason: self.stop_reason.take(),
+            },
+        );
+        self.exits.truncate(MAX_EXIT_HISTORY);
+    }
+
+    /// Restarts the process or warns about it once its usage stayed above
+    /// `max_memory` or `max_cpu_percent` for the whole limit window
+    pub fn check_limits(&mut self) {
+        let usage = self.usage.current().filter(|_| self.child.is_some());
+
+        let memory = self
+            .max_memory
+            .zip(usage)
+            .filter(|(max, u)| u.rss_bytes > *max);
+        let cpu = self
+            .max_cpu_percent
+            .zip(usage)
+            .filter(|(max, u)| u.cpu_percent > *max);
+
+        let memory_for = exceeded_for(&mut self.memory_over_since, memory.is_some());
+        let cpu_for = exceeded_for(&mut self.cpu_over_since, cpu.is_some());
+
+        let window = format_duration(self.limit_window);
+        let reason = match (memory, cpu) {
+            (Some((max, u)), _) if memory_for >= Some(self.limit_window) => format!(
+                "Memory {} above limit {} for {}",
+                format_bytes(u.rss_bytes),
+                format_bytes(max),
+                window
+            ),
+            (_, Some((max, u))) if cpu_for >= Some(self.limit_window) => format!(
+                "CPU {:.0}% above limit {:.0}% for {}",
+                u.cpu_percent, max, window
+            ),
+            _ => {
+                if memory.is_none() && cpu.is_none() {
+                    self.limit_warning = None;
                 }
-            });
+                return;
+            }
+        };
+
+        match self.limit_action() {
+            LimitAction::Restart => {
+                self.push_log(format!("{reason}, restarting"));
+                self.memory_over_since = None;
+                self.cpu_over_since = None;
+                self.stop_reason = Some(reason);
+                self.restart();
+            }
+            LimitAction::Warn => {
+                if self.limit_warning.is_none() {
+                    self.push_log(&reason);
+                }
+                self.limit_warning = Some(reason);
+            }
+        }
+    }
+
+    /// Tasks are never restarted automatically, so they only get a warning
+    pub fn limit_action(&self) -> LimitAction {
+        if self.is_task() {
+            LimitAction::Warn
+        } else {
+            self.on_limit
         }
     }
 
+    pub fn accepts_input(&self) -> bool {
+        self.pty_master.is_some() || self.stdin.is_some()
+    }
+
+    /// Writes to the process's pty or stdin, with the keys of a terminal
+    pub fn send_input(&mut self, bytes: &[u8]) {
+        let result = match (&mut self.pty_master, &mut self.stdin) {
+            (Some(master), _) => master.write_all(bytes),
+            (None, Some(stdin)) => {
+                // Without a pty nothing turns Enter's \r into a newline
+                let bytes: Vec<u8> = bytes
+                    .iter()
+                    .map(|b| if *b == b'\r' { b'\n' } else { *b })
+                    .collect();
+                stdin.write_all(&bytes).and_then(|_| stdin.flush())
+            }
+            (None, None) => return,
+        };
+
+        if let Err(e) = result {
+            self.push_log(format!("Failed to send input: {}", e));
+        }
+    }
+
+    /// Tells a process running on a pty about the new size of the log view
+    pub fn resize_pty(&self, size: (u16, u16)) {
+        if let Some(ref master) = self.pty_master
+            && let Err(e) = pty::resize(master, size)
+        {
+            self.push_log(format!("Failed to resize pty: {}", e));
+        }
+    }
+
+    pub fn pid(&self) -> Option<u32> {
+        self.child.as_ref().map(|x| x.id())
+    }
+
+    /// The working directory as an absolute path, if it exists
+    pub fn resolved_cwd(&self) -> String {
+        let cwd = self.cwd.as_deref().unwrap_or(".");
+
+        match Path::new(cwd).canonicalize() {
+            Ok(path) => path.display().to_string(),
+            Err(e) => format!("{cwd} ({e})"),
+        }
+    }
+
+    /// The environment the process runs with: ours plus its configured
+    /// variables, the latter marked with `true`
+    pub fn effective_env(&self) -> BTreeMap<String, (String, bool)> {
+        let mut env: BTreeMap<String, (String, bool)> =
+            std::env::vars().map(|(k, v)| (k, (v, false))).collect();
+
+        for (k, v) in &self.env {
+            env.insert(k.clone(), (v.clone(), true));
+        }
+
+        env
+    }
+
+    fn set_status(&self, status: Option<String>) {
+        *self.special_status.lock().unwrap() = status;
+    }
+
     fn push_log<S: Into<String>>(&self, msg: S) {
-        let v = msg.into();
-        let mut logs = self.logs.lock().unwrap();
-        logs.push(v);
+        push_line(&self.logs, msg);
+    }
+}
+
+/// Runs a command to completion with its output going to the process logs
+/// Runs a command to completion, logging its output. With a timeout it's
+/// killed once that passes, or once stop hooks are cancelled.
+pub fn run_command(
+    args: &[String],
+    cwd: Option<&str>,
+    env: &HashMap<String, String>,
+    logs: &Logs,
+    timeout: Option<Duration>,
+) -> io::Result<ExitStatus> {
+    let Some((program, rest)) = args.split_first() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Command is empty"));
+    };
+
+    let mut cmd = Command::new(program);
+    cmd.args(rest);
+    cmd.envs(env);
+
+    if let Some(dir) = cwd {
+        cmd.current_dir(dir);
+    }
+
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+
+    // In its own process group, so a timeout kills whatever it started too
+    if timeout.is_some() {
+        cmd.process_group(0);
+    }
+
+    let mut child = cmd.spawn()?;
+
+    let readers = [
+        spawn_reader(child.stdout.take(), logs),
+        spawn_reader(child.stderr.take(), logs),
+    ];
+
+    let status = match timeout {
+        Some(timeout) => wait_with_timeout(&mut child, timeout),
+        None => child.wait(),
+    };
+
+    // Let the remaining output land in the logs before the result does, without
+    // hanging on background children that inherited the pipes
+    let start = Instant::now();
+    while start.elapsed() < READER_DRAIN_TIMEOUT
+        && readers.iter().flatten().any(|x| !x.is_finished())
+    {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    status
+}
+
+/// Runs the build unless its inputs are unchanged since the last successful one
+fn run_build(
+    name: &str,
+    args: &[String],
+    inputs: &[String],
+    cwd: Option<&str>,
+    env: &HashMap<String, String>,
+    logs: &Logs,
+) -> Result<(), String> {
+    let hash = if inputs.is_empty() {
+        None
+    } else {
+        match build::hash_inputs(Path::new(cwd.unwrap_or(".")), inputs) {
+            Ok(hash) => Some(hash),
+            Err(e) => {
+                push_line(logs, format!("Failed to hash build inputs: {}", e));
+                None
+            }
+        }
+    };
+
+    if hash.is_some() && hash == build::cached_hash(name) {
+        push_line(logs, "Build inputs unchanged, skipping build");
+        return Ok(());
     }
+
+    run_hook("build", args, cwd, env, logs, None)?;
+
+    if let Some(hash) = hash
+        && let Err(e) = build::store_hash(name, hash)
+    {
+        push_line(logs, format!("Failed to store build hash: {}", e));
+    }
+
+    Ok(())
+}
+
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> io::Result<ExitStatus> {
+    let start = Instant::now();
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+
+        let cancelled = CANCEL_STOP_HOOKS.load(Ordering::Relaxed);
+        if cancelled || start.elapsed() >= timeout {
+            let _ = killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL);
+            child.wait()?;
+
+            let reason = if cancelled {
+                "cancelled".to_string()
+            } else {
+                format!("timed out after {}", format_duration(timeout))
+            };
+            return Err(io::Error::new(io::ErrorKind::TimedOut, reason));
+        }
+
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Kills every running stop hook, and any started later, when exiting
+/// without waiting for them
+pub fn cancel_stop_hooks() {
+    CANCEL_STOP_HOOKS.store(true, Ordering::Relaxed);
+}
+
+fn run_hook(
+    name: &str,
+    args: &[String],
+    cwd: Option<&str>,
+    env: &HashMap<String, String>,
+    logs: &Logs,
+    timeout: Option<Duration>,
+) -> Result<(), String> {
+    push_line(logs, format!("Running {}: {}", name, args.join(" ")));
+
+    let error = match run_command(args, cwd, env, logs, timeout) {
+        Ok(status) if status.success() => return Ok(()),
+        Ok(status) => format_exit(&status),
+        Err(e) => e.to_string(),
+    };
+
+    push_line(logs, format!("{} failed: {}", name, error));
+    Err(error)
+}
+
+fn spawn_reader(
+    stream: Option<impl std::io::Read + Send + 'static>,
+    logs: &Logs,
+) -> Option<JoinHandle<()>> {
+    let stream = stream?;
+    let logs = logs.clone();
+
+    Some(thread::spawn(move || logs::read_into(stream, &logs)))
+}
+
+pub fn push_line<S: Into<String>>(logs: &Mutex<LogBuffer>, line: S) {
+    logs::push(logs, line.into());
+}
+
+/// How long a limit has been exceeded, tracking when it started in `since`
+fn exceeded_for(since: &mut Option<Instant>, over: bool) -> Option<Duration> {
+    if !over {
+        *since = None;
+        return None;
+    }
+
+    Some(since.get_or_insert_with(Instant::now).elapsed())
 }
 
 fn pid_from_port(port: u16) -> Option<String> {
@@ -276,19 +1372,76 @@ fn pid_from_port(port: u16) -> Option<String> {
     let stdout = String::from_utf8_lossy(&output.stdout);
 
     for line in stdout.lines() {
-        if line.contains(&format!(":{}", port)) {
-            if let Some(start) = line.find("pid=") {
-                let pid_part = &line[start + 4..];
-                let pid: String = pid_part
-                    .chars()
-                    .take_while(|c| c.is_ascii_digit())
-                    .collect();
-                if !pid.is_empty() {
-                    return Some(pid);
-                }
+        if line.contains(&format!(":{}", port))
+            && let Some(start) = line.find("pid=")
+        {
+            let pid_part = &line[start + 4..];
+            let pid: String = pid_part
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            if !pid.is_empty() {
+                return Some(pid);
             }
         }
     }
 
     None
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::Usage;
+
+    fn process(config: &str) -> ManagedProcess {
+        let config: ProcessConfig = toml::from_str(config).unwrap();
+        ManagedProcess::new(&config, 1)
+    }
+
+    /// Starts the process and makes it look like it's been above its CPU
+    /// limit for the whole window
+    fn over_cpu_limit(p: &mut ManagedProcess) {
+        p.start();
+        p.limit_window = Duration::ZERO;
+        p.usage.history.push_back(Usage {
+            cpu_percent: 100.0,
+            ..Usage::default()
+        });
+    }
+
+    #[test]
+    fn services_restart_above_limits() {
+        let mut p = process(r#"
+            name = "service"
+            cmd = ["sleep", "10"]
+            max_cpu_percent = 50.0
+        "#);
+        over_cpu_limit(&mut p);
+        let pid = p.pid();
+
+        p.check_limits();
+        assert!(p.pid().is_some() && p.pid() != pid);
+        assert!(p.limit_warning.is_none());
+
+        p.kill();
+    }
+
+    #[test]
+    fn tasks_only_warn_above_limits() {
+        let mut p = process(r#"
+            name = "task"
+            type = "task"
+            cmd = ["sleep", "10"]
+            max_cpu_percent = 50.0
+        "#);
+        over_cpu_limit(&mut p);
+        let pid = p.pid();
+
+        p.check_limits();
+        assert!(pid.is_some() && p.pid() == pid);
+        assert!(p.limit_warning.is_some());
+
+        p.kill();
+    }
+}
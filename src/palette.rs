@@ -0,0 +1,211 @@
+use std::{fs, io, path::Path};
+
+use ratatui::widgets::ListState;
+
+use crate::keybinds::KeybindType;
+
+const HISTORY_FILE: &str = ".dawn/palette_history";
+const MAX_HISTORY: usize = 50;
+
+#[derive(Clone)]
+pub enum PaletteAction {
+    /// Runs the action as if its key was pressed in the process list, with
+    /// the named process selected first
+    Key(KeybindType, Option<String>),
+    Profile(Option<String>),
+}
+
+#[derive(Clone)]
+pub struct Command {
+    pub label: String,
+    pub action: PaletteAction,
+}
+
+impl Command {
+    pub fn new<T: Into<String>>(label: T, action: PaletteAction) -> Self {
+        Self {
+            label: label.into(),
+            action,
+        }
+    }
+}
+
+pub struct Palette {
+    pub input: String,
+    pub state: ListState,
+    commands: Vec<Command>,
+    /// Indices into `commands`, best match first
+    matches: Vec<usize>,
+}
+
+impl Palette {
+    /// Commands that are in the history are listed first, most recent first
+    pub fn new(mut commands: Vec<Command>, history: &[String]) -> Self {
+        let recency = |c: &Command| {
+            history
+                .iter()
+                .position(|x| *x == c.label)
+                .unwrap_or(history.len())
+        };
+        commands.sort_by_key(|c| recency(c));
+
+        let mut palette = Self {
+            input: String::new(),
+            state: ListState::default(),
+            commands,
+            matches: vec![],
+        };
+        palette.update_matches();
+
+        palette
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.input.push(c);
+        self.update_matches();
+    }
+
+    pub fn pop(&mut self) {
+        self.input.pop();
+        self.update_matches();
+    }
+
+    pub fn next(&mut self) {
+        if !self.matches.is_empty() {
+            let i = self.state.selected().unwrap_or(0);
+            self.state.select(Some((i + 1) % self.matches.len()));
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if !self.matches.is_empty() {
+            let i = self.state.selected().unwrap_or(0);
+            self.state
+                .select(Some((i + self.matches.len() - 1) % self.matches.len()));
+        }
+    }
+
+    pub fn matches(&self) -> impl Iterator<Item = &Command> {
+        self.matches.iter().map(|i| &self.commands[*i])
+    }
+
+    pub fn selected(&self) -> Option<&Command> {
+        let i = self.matches.get(self.state.selected()?)?;
+        self.commands.get(*i)
+    }
+
+    fn update_matches(&mut self) {
+        let mut scored: Vec<(i64, usize)> = self
+            .commands
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| Some((fuzzy_score(&self.input, &c.label)?, i)))
+            .collect();
+
+        // Stable, so equal scores keep the history order
+        scored.sort_by_key(|x| -x.0);
+
+        self.matches = scored.into_iter().map(|x| x.1).collect();
+        self.state
+            .select(if self.matches.is_empty() { None } else { Some(0) });
+    }
+}
+
+/// Scores `candidate` if every non-space character of `query` appears in it
+/// in order, preferring consecutive matches and matches at word starts
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    if query.trim().is_empty() {
+        return Some(0);
+    }
+
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last: Option<usize> = None;
+
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = pos + candidate[pos..].iter().position(|c| *c == q)?;
+
+        score += 1;
+        if found == 0 || candidate[found - 1] == ' ' {
+            score += 8;
+        }
+        match last {
+            Some(l) if l + 1 == found => score += 5,
+            Some(l) => score -= (found - l - 1).min(5) as i64,
+            None => {}
+        }
+
+        last = Some(found);
+        pos = found + 1;
+    }
+
+    // Shorter commands first when they match equally well
+    Some(score * 100 - candidate.len() as i64)
+}
+
+pub fn load_history() -> Vec<String> {
+    fs::read_to_string(HISTORY_FILE)
+        .map(|x| x.lines().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+/// Moves `label` to the front of the history and writes it to disk
+pub fn record_history(history: &mut Vec<String>, label: &str) -> io::Result<()> {
+    history.retain(|x| x != label);
+    history.insert(0, label.to_string());
+    history.truncate(MAX_HISTORY);
+
+    if let Some(dir) = Path::new(HISTORY_FILE).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(HISTORY_FILE, history.join("\n") + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rank<'a>(query: &str, candidates: &[&'a str]) -> Vec<&'a str> {
+        let mut scored: Vec<_> = candidates
+            .iter()
+            .filter_map(|x| fuzzy_score(query, x).map(|score| (score, *x)))
+            .collect();
+        scored.sort_by_key(|x| std::cmp::Reverse(x.0));
+        scored.into_iter().map(|x| x.1).collect()
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert_eq!(fuzzy_score("", "Start api"), Some(0));
+        assert_eq!(fuzzy_score("  ", "Start api"), Some(0));
+    }
+
+    #[test]
+    fn characters_must_appear_in_order() {
+        assert!(fuzzy_score("sa", "Start api").is_some());
+        assert!(fuzzy_score("as", "Start").is_none());
+        assert!(fuzzy_score("x", "Start api").is_none());
+        assert!(fuzzy_score("starts", "Start").is_none());
+    }
+
+    #[test]
+    fn ignores_case_and_spaces() {
+        assert_eq!(fuzzy_score("START", "start api"), fuzzy_score("start", "start api"));
+        assert_eq!(fuzzy_score("st api", "Start api"), fuzzy_score("stapi", "Start api"));
+    }
+
+    #[test]
+    fn prefers_word_starts_and_consecutive_matches() {
+        assert_eq!(
+            rank("sa", &["Restart web", "Start api", "Stop all"]),
+            ["Stop all", "Start api", "Restart web"]
+        );
+        assert_eq!(rank("web", &["Stop web worker", "Start web"]), ["Start web", "Stop web worker"]);
+    }
+
+    #[test]
+    fn prefers_shorter_candidates() {
+        assert_eq!(rank("stop", &["Stop all", "Stop"]), ["Stop", "Stop all"]);
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    fs::File,
+    io,
+    os::{fd::AsRawFd, unix::process::CommandExt},
+    process::{Command, Stdio},
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use nix::{
+    libc,
+    pty::{openpty, Winsize},
+};
+
+const DEFAULT_SIZE: (u16, u16) = (80, 24);
+
+/// Opens a pseudo-terminal of the given size and attaches the command's
+/// stdio to it, returning the master side to read output from
+pub fn attach(cmd: &mut Command, (cols, rows): (u16, u16)) -> io::Result<File> {
+    let pty = openpty(&winsize(cols, rows), None)?;
+
+    // openpty doesn't set close-on-exec, and a leaked master would keep the
+    // process (or any other process we start) from seeing the pty close
+    for fd in [pty.master.as_raw_fd(), pty.slave.as_raw_fd()] {
+        if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    cmd.stdin(Stdio::from(pty.slave.try_clone()?));
+    cmd.stdout(Stdio::from(pty.slave.try_clone()?));
+    cmd.stderr(Stdio::from(pty.slave));
+
+    // The child needs its own session with the pty as controlling terminal
+    // for job control, Ctrl+C handling and SIGWINCH to work
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY, 0) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    Ok(File::from(pty.master))
+}
+
+/// Changes the pty size, which sends SIGWINCH to the process
+pub fn resize(master: &File, (cols, rows): (u16, u16)) -> io::Result<()> {
+    let size = winsize(cols, rows);
+
+    if unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// The size of the log view, which is what output is shown in
+pub fn log_view_size() -> (u16, u16) {
+    let (cols, rows) = crossterm::terminal::size().unwrap_or(DEFAULT_SIZE);
+    log_view_size_for(cols, rows)
+}
+
+/// The log view inside its border for a terminal of the given size
+pub fn log_view_size_for(cols: u16, rows: u16) -> (u16, u16) {
+    (cols.saturating_sub(2).max(1), rows.saturating_sub(2).max(1))
+}
+
+/// The bytes a terminal sends for the key, `None` for keys without one
+pub fn key_bytes(key: &KeyEvent) -> Option<Vec<u8>> {
+    let mut bytes = match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => match c {
+            'a'..='z' | 'A'..='Z' => vec![c.to_ascii_lowercase() as u8 - b'a' + 1],
+            '@' | ' ' => vec![0],
+            '[' => vec![0x1b],
+            '\\' => vec![0x1c],
+            ']' => vec![0x1d],
+            '^' => vec![0x1e],
+            '_' => vec![0x1f],
+            _ => return None,
+        },
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Esc => vec![0x1b],
+        KeyCode::Up => b"\x1b[A".to_vec(),
+        KeyCode::Down => b"\x1b[B".to_vec(),
+        KeyCode::Right => b"\x1b[C".to_vec(),
+        KeyCode::Left => b"\x1b[D".to_vec(),
+        KeyCode::Home => b"\x1b[H".to_vec(),
+        KeyCode::End => b"\x1b[F".to_vec(),
+        KeyCode::Insert => b"\x1b[2~".to_vec(),
+        KeyCode::Delete => b"\x1b[3~".to_vec(),
+        KeyCode::PageUp => b"\x1b[5~".to_vec(),
+        KeyCode::PageDown => b"\x1b[6~".to_vec(),
+        KeyCode::F(n @ 1..=4) => vec![0x1b, b'O', b'P' + n - 1],
+        KeyCode::F(n) => {
+            let code = match n {
+                5 => 15,
+                6..=10 => n + 11,
+                11..=12 => n + 12,
+                _ => return None,
+            };
+            format!("\x1b[{code}~").into_bytes()
+        }
+        _ => return None,
+    };
+
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        bytes.insert(0, 0x1b);
+    }
+
+    Some(bytes)
+}
+
+fn winsize(cols: u16, rows: u16) -> Winsize {
+    Winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(code: KeyCode, modifiers: KeyModifiers) -> Option<Vec<u8>> {
+        key_bytes(&KeyEvent::new(code, modifiers))
+    }
+
+    #[test]
+    fn characters() {
+        assert_eq!(bytes(KeyCode::Char('a'), KeyModifiers::NONE), Some(b"a".to_vec()));
+        assert_eq!(bytes(KeyCode::Char('A'), KeyModifiers::SHIFT), Some(b"A".to_vec()));
+        assert_eq!(bytes(KeyCode::Char('é'), KeyModifiers::NONE), Some("é".as_bytes().to_vec()));
+    }
+
+    #[test]
+    fn control_characters() {
+        assert_eq!(bytes(KeyCode::Char('a'), KeyModifiers::CONTROL), Some(vec![1]));
+        assert_eq!(bytes(KeyCode::Char('c'), KeyModifiers::CONTROL), Some(vec![3]));
+        assert_eq!(bytes(KeyCode::Char('Z'), KeyModifiers::CONTROL), Some(vec![26]));
+        assert_eq!(bytes(KeyCode::Char(' '), KeyModifiers::CONTROL), Some(vec![0]));
+        assert_eq!(bytes(KeyCode::Char('['), KeyModifiers::CONTROL), Some(vec![0x1b]));
+        assert_eq!(bytes(KeyCode::Char('1'), KeyModifiers::CONTROL), None);
+    }
+
+    #[test]
+    fn special_keys() {
+        assert_eq!(bytes(KeyCode::Enter, KeyModifiers::NONE), Some(b"\r".to_vec()));
+        assert_eq!(bytes(KeyCode::Backspace, KeyModifiers::NONE), Some(vec![0x7f]));
+        assert_eq!(bytes(KeyCode::Up, KeyModifiers::NONE), Some(b"\x1b[A".to_vec()));
+        assert_eq!(bytes(KeyCode::Left, KeyModifiers::NONE), Some(b"\x1b[D".to_vec()));
+        assert_eq!(bytes(KeyCode::BackTab, KeyModifiers::SHIFT), Some(b"\x1b[Z".to_vec()));
+        assert_eq!(bytes(KeyCode::F(1), KeyModifiers::NONE), Some(b"\x1bOP".to_vec()));
+        assert_eq!(bytes(KeyCode::F(5), KeyModifiers::NONE), Some(b"\x1b[15~".to_vec()));
+        assert_eq!(bytes(KeyCode::F(12), KeyModifiers::NONE), Some(b"\x1b[24~".to_vec()));
+        assert_eq!(bytes(KeyCode::F(13), KeyModifiers::NONE), None);
+    }
+
+    #[test]
+    fn alt_prefixes_escape() {
+        assert_eq!(bytes(KeyCode::Char('x'), KeyModifiers::ALT), Some(b"\x1bx".to_vec()));
+        assert_eq!(
+            bytes(KeyCode::Char('x'), KeyModifiers::ALT | KeyModifiers::CONTROL),
+            Some(vec![0x1b, 0x18])
+        );
+        assert_eq!(bytes(KeyCode::Up, KeyModifiers::ALT), Some(b"\x1b\x1b[A".to_vec()));
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    sync::LazyLock,
+    time::Instant,
+};
+
+use nix::unistd::{SysconfVar, sysconf};
+
+/// Times in /proc are in clock ticks per second. This is 100 on most systems,
+/// which is the fallback if it can't be read.
+static CLOCK_TICKS: LazyLock<f64> = LazyLock::new(|| match sysconf(SysconfVar::CLK_TCK) {
+    Ok(Some(ticks)) if ticks > 0 => ticks as f64,
+    _ => 100.0,
+});
+const HISTORY_LEN: usize = 60;
+
+#[derive(Clone, Copy, Default)]
+pub struct Usage {
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    pub threads: u64,
+}
+
+struct ProcStat {
+    ppid: u32,
+    /// User plus system time in clock ticks
+    cpu_ticks: u64,
+}
+
+/// A snapshot of every process in /proc, read once per sample and shared
+/// between all managed processes
+pub struct ProcTable {
+    stats: HashMap<u32, ProcStat>,
+    children: HashMap<u32, Vec<u32>>,
+    taken_at: Instant,
+}
+
+impl ProcTable {
+    pub fn read() -> Self {
+        let mut stats = HashMap::new();
+        let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+
+        for entry in fs::read_dir("/proc").into_iter().flatten().flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|x| x.parse().ok()) else {
+                continue;
+            };
+            let Some(stat) = read_stat(pid) else {
+                continue;
+            };
+
+            children.entry(stat.ppid).or_default().push(pid);
+            stats.insert(pid, stat);
+        }
+
+        Self {
+            stats,
+            children,
+            taken_at: Instant::now(),
+        }
+    }
+
+    /// `pid` and all of its descendants that are still alive
+    fn tree(&self, pid: u32) -> Vec<u32> {
+        let mut out = vec![];
+        let mut stack = vec![pid];
+
+        while let Some(pid) = stack.pop() {
+            if self.stats.contains_key(&pid) {
+                out.push(pid);
+                stack.extend(self.children.get(&pid).into_iter().flatten());
+            }
+        }
+
+        out
+    }
+}
+
+/// Resource usage of a process tree over time
+#[derive(Default)]
+pub struct UsageTracker {
+    pub history: VecDeque<Usage>,
+    /// Ticks per pid at the previous sample, so exited children don't make
+    /// the total go backwards
+    last_ticks: HashMap<u32, u64>,
+    last_sample: Option<Instant>,
+}
+
+impl UsageTracker {
+    pub fn current(&self) -> Option<Usage> {
+        self.history.back().copied()
+    }
+
+    pub fn sample(&mut self, pid: Option<u32>, table: &ProcTable) {
+        let Some(pid) = pid else {
+            self.last_ticks.clear();
+            self.last_sample = None;
+            return;
+        };
+
+        let mut usage = Usage::default();
+        let mut delta_ticks = 0;
+        let mut ticks = HashMap::new();
+
+        for pid in table.tree(pid) {
+            let cpu_ticks = table.stats[&pid].cpu_ticks;
+            delta_ticks += cpu_ticks.saturating_sub(*self.last_ticks.get(&pid).unwrap_or(&cpu_ticks));
+            ticks.insert(pid, cpu_ticks);
+
+            let (rss, threads) = read_status(pid);
+            usage.rss_bytes += rss;
+            usage.threads += threads;
+        }
+
+        if let Some(last) = self.last_sample {
+            let elapsed = table.taken_at.duration_since(last).as_secs_f64();
+            if elapsed > 0.0 {
+                usage.cpu_percent = delta_ticks as f64 / *CLOCK_TICKS / elapsed * 100.0;
+            }
+        }
+
+        self.last_ticks = ticks;
+        self.last_sample = Some(table.taken_at);
+
+        self.history.push_back(usage);
+        if self.history.len() > HISTORY_LEN {
+            self.history.pop_front();
+        }
+    }
+}
+
+fn read_stat(pid: u32) -> Option<ProcStat> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+
+    // The command name may contain spaces and parentheses, so fields are
+    // counted from the last ')'
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+
+    // Fields 4 (ppid), 14 (utime) and 15 (stime), the first here being 3
+    let ppid = fields.get(1)?.parse().ok()?;
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+
+    Some(ProcStat {
+        ppid,
+        cpu_ticks: utime + stime,
+    })
+}
+
+/// RSS in bytes and thread count, zero if the process is gone
+fn read_status(pid: u32) -> (u64, u64) {
+    let Ok(status) = fs::read_to_string(format!("/proc/{pid}/status")) else {
+        return (0, 0);
+    };
+
+    let mut rss = 0;
+    let mut threads = 0;
+
+    for line in status.lines() {
+        if let Some(value) = line.strip_prefix("VmRSS:") {
+            // Reported in kB
+            rss = value.trim().trim_end_matches("kB").trim().parse::<u64>().unwrap_or(0) * 1024;
+        } else if let Some(value) = line.strip_prefix("Threads:") {
+            threads = value.trim().parse().unwrap_or(0);
+        }
+    }
+
+    (rss, threads)
+}
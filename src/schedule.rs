@@ -0,0 +1,230 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, TimeZone, Timelike};
+
+/// A standard five field cron expression: minute, hour, day of month, month
+/// and day of week. Fields support `*`, `a`, `a-b`, `/step` and lists.
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // Like cron, when both days and weekdays are restricted either may match.
+    // A field starting with `*`, e.g. `*/2`, doesn't count as restricted.
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Schedule {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("Expected 5 fields in schedule: {expr}"));
+        };
+
+        let mut weekday_bits = parse_field(weekdays, 0, 7)?;
+        // Both 0 and 7 mean Sunday
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits |= 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays: weekday_bits,
+            days_restricted: !days.starts_with('*'),
+            weekdays_restricted: !weekdays.starts_with('*'),
+        })
+    }
+
+    /// The first matching minute after `after`
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let mut t = after.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = t + Duration::days(366 * 5);
+
+        while t < limit {
+            if !has(self.months, t.month()) {
+                t = start_of_next_month(t)?;
+                continue;
+            }
+
+            if !self.day_matches(t) {
+                t = t.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+
+            if !has(self.hours, t.hour()) {
+                t = t.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+
+            if !has(self.minutes, t.minute()) {
+                t += Duration::minutes(1);
+                continue;
+            }
+
+            // Skips local times that don't exist due to DST changes
+            match Local.from_local_datetime(&t).earliest() {
+                Some(time) => return Some(time),
+                None => t += Duration::minutes(1),
+            }
+        }
+
+        None
+    }
+
+    fn day_matches(&self, t: NaiveDateTime) -> bool {
+        let day = has(self.days, t.day());
+        let weekday = has(self.weekdays, t.weekday().num_days_from_sunday());
+
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+fn has(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+fn start_of_next_month(t: NaiveDateTime) -> Option<NaiveDateTime> {
+    let (year, month) = match t.month() {
+        12 => (t.year() + 1, 1),
+        m => (t.year(), m + 1),
+    };
+
+    t.date()
+        .with_day(1)?
+        .with_month(month)?
+        .with_year(year)?
+        .and_hms_opt(0, 0, 0)
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| format!("Invalid step in schedule: {part}"))?;
+                (range, step.max(1))
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (parse_value(a, min, max)?, parse_value(b, min, max)?)
+        } else {
+            let value = parse_value(range, min, max)?;
+            // `5/10` means every 10 starting at 5
+            (value, if part.contains('/') { max } else { value })
+        };
+
+        if start > end {
+            return Err(format!("Invalid range in schedule: {part}"));
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok(bits)
+}
+
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(v) if (min..=max).contains(&v) => Ok(v),
+        _ => Err(format!("Schedule value {value} is not within {min}-{max}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+    }
+
+    fn next(expr: &str, after: DateTime<Local>) -> DateTime<Local> {
+        Schedule::parse(expr).unwrap().next_after(after).unwrap()
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert!(Schedule::parse("* * * *").is_err());
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("* * 0 * *").is_err());
+        assert!(Schedule::parse("30-10 * * * *").is_err());
+        assert!(Schedule::parse("*/x * * * *").is_err());
+    }
+
+    #[test]
+    fn next_is_strictly_after() {
+        assert_eq!(next("* * * * *", at(2025, 6, 2, 10, 7)), at(2025, 6, 2, 10, 8));
+        assert_eq!(next("7 10 * * *", at(2025, 6, 2, 10, 7)), at(2025, 6, 3, 10, 7));
+    }
+
+    #[test]
+    fn steps() {
+        assert_eq!(next("*/15 * * * *", at(2025, 6, 2, 10, 7)), at(2025, 6, 2, 10, 15));
+        assert_eq!(next("*/15 * * * *", at(2025, 6, 2, 10, 45)), at(2025, 6, 2, 11, 0));
+        assert_eq!(next("10-30/10 * * * *", at(2025, 6, 2, 10, 20)), at(2025, 6, 2, 10, 30));
+        assert_eq!(next("10-30/10 * * * *", at(2025, 6, 2, 10, 31)), at(2025, 6, 2, 11, 10));
+        // A single value with a step runs to the end of the range
+        assert_eq!(next("5/20 * * * *", at(2025, 6, 2, 10, 30)), at(2025, 6, 2, 10, 45));
+        assert_eq!(next("0 0-23/6 * * *", at(2025, 6, 2, 13, 0)), at(2025, 6, 2, 18, 0));
+    }
+
+    #[test]
+    fn lists() {
+        let schedule = "0,30 9,17 * * *";
+        assert_eq!(next(schedule, at(2025, 6, 2, 9, 0)), at(2025, 6, 2, 9, 30));
+        assert_eq!(next(schedule, at(2025, 6, 2, 9, 30)), at(2025, 6, 2, 17, 0));
+        assert_eq!(next(schedule, at(2025, 6, 2, 17, 30)), at(2025, 6, 3, 9, 0));
+    }
+
+    #[test]
+    fn month_rollover() {
+        assert_eq!(next("0 0 1 * *", at(2025, 1, 31, 12, 0)), at(2025, 2, 1, 0, 0));
+        assert_eq!(next("0 0 1 * *", at(2025, 12, 15, 0, 0)), at(2026, 1, 1, 0, 0));
+        assert_eq!(next("59 23 31 * *", at(2025, 12, 31, 23, 59)), at(2026, 1, 31, 23, 59));
+        // Months without the day are skipped
+        assert_eq!(next("0 0 31 * *", at(2025, 4, 1, 0, 0)), at(2025, 5, 31, 0, 0));
+        assert_eq!(next("0 0 29 2 *", at(2025, 3, 1, 0, 0)), at(2028, 2, 29, 0, 0));
+    }
+
+    #[test]
+    fn day_of_month_or_day_of_week() {
+        // 2025-06-08 is a Sunday. With both restricted either may match.
+        let schedule = "0 0 10 * 1";
+        assert_eq!(next(schedule, at(2025, 6, 8, 0, 0)), at(2025, 6, 9, 0, 0));
+        assert_eq!(next(schedule, at(2025, 6, 9, 0, 0)), at(2025, 6, 10, 0, 0));
+        assert_eq!(next(schedule, at(2025, 6, 10, 0, 0)), at(2025, 6, 16, 0, 0));
+
+        // With only one restricted, that one decides
+        assert_eq!(next("0 0 10 * *", at(2025, 6, 8, 0, 0)), at(2025, 6, 10, 0, 0));
+        assert_eq!(next("0 0 * * 1", at(2025, 6, 10, 0, 0)), at(2025, 6, 16, 0, 0));
+
+        // A stepped `*` isn't restricted, so both have to match: an odd day
+        // that is a Monday, and the 1st on a Sunday, Tuesday, Thursday or
+        // Saturday
+        assert_eq!(next("0 0 */2 * 1", at(2025, 6, 9, 0, 0)), at(2025, 6, 23, 0, 0));
+        assert_eq!(next("0 0 1 * */2", at(2025, 6, 2, 0, 0)), at(2025, 7, 1, 0, 0));
+    }
+
+    #[test]
+    fn sunday_is_0_and_7() {
+        assert_eq!(next("0 0 * * 0", at(2025, 6, 9, 0, 0)), at(2025, 6, 15, 0, 0));
+        assert_eq!(next("0 0 * * 7", at(2025, 6, 9, 0, 0)), at(2025, 6, 15, 0, 0));
+        assert_eq!(next("0 0 * * 5-7", at(2025, 6, 9, 0, 0)), at(2025, 6, 13, 0, 0));
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::config::UpdateStep;
+use crate::logs::Logs;
+use crate::managed_process::{push_line, run_command};
+use crate::util::format_exit;
+
+#[derive(Clone)]
+pub enum StepState {
+    Pending,
+    Running(Instant),
+    Succeeded(Duration),
+    Failed(Duration, String),
+    Skipped,
+}
+
+#[derive(Clone)]
+pub struct StepProgress {
+    pub name: String,
+    pub state: StepState,
+}
+
+impl StepProgress {
+    pub fn pending(name: String) -> Self {
+        Self {
+            name,
+            state: StepState::Pending,
+        }
+    }
+}
+
+/// Runs the steps in order, stopping at the first failing step unless it has
+/// `continue_on_error` set. `phase` is kept up to date for the process list.
+pub fn run_steps(
+    steps: &[UpdateStep],
+    cwd: Option<&str>,
+    env: &HashMap<String, String>,
+    logs: &Logs,
+    progress: &Mutex<Vec<StepProgress>>,
+    phase: impl Fn(String),
+) -> Result<(), String> {
+    let set_state = |i: usize, state: StepState| {
+        if let Some(step) = progress.lock().unwrap().get_mut(i) {
+            step.state = state;
+        }
+    };
+
+    for (i, step) in steps.iter().enumerate() {
+        let name = step.display_name();
+        phase(format!("Updating {}/{}", i + 1, steps.len()));
+        push_line(logs, format!("Step {}/{}: {}", i + 1, steps.len(), name));
+
+        let dir = match &step.cwd {
+            Some(dir) => Some(Path::new(cwd.unwrap_or(".")).join(dir)),
+            None => cwd.map(PathBuf::from),
+        };
+
+        let start = Instant::now();
+        set_state(i, StepState::Running(start));
+
+        let dir = dir.as_ref().map(|x| x.to_string_lossy().to_string());
+        let result = match run_command(&step.cmd, dir.as_deref(), env, logs, None) {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => Err(format_exit(&status)),
+            Err(e) => Err(e.to_string()),
+        };
+
+        match result {
+            Ok(()) => set_state(i, StepState::Succeeded(start.elapsed())),
+            Err(e) => {
+                set_state(i, StepState::Failed(start.elapsed(), e.clone()));
+                push_line(logs, format!("Step {} failed: {}", name, e));
+
+                if step.continue_on_error {
+                    continue;
+                }
+
+                for j in i + 1..steps.len() {
+                    set_state(j, StepState::Skipped);
+                }
+
+                return Err(if steps.len() > 1 {
+                    format!("{name}: {e}")
+                } else {
+                    e
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
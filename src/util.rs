@@ -1,13 +1,29 @@
+use chrono::{DateTime, Local};
 use crossterm::event::KeyCode;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
 use std::time::Duration;
 
 pub fn keycode_display(code: &KeyCode) -> String {
     match code {
+        KeyCode::Char(' ') => "Space".into(),
         KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("F{n}"),
         KeyCode::Enter => "Enter".into(),
         KeyCode::Esc => "Esc".into(),
         KeyCode::Up => "↑".into(),
         KeyCode::Down => "↓".into(),
+        KeyCode::Left => "←".into(),
+        KeyCode::Right => "→".into(),
+        KeyCode::Tab => "Tab".into(),
+        KeyCode::BackTab => "Shift+Tab".into(),
+        KeyCode::Backspace => "Backspace".into(),
+        KeyCode::Delete => "Del".into(),
+        KeyCode::Insert => "Ins".into(),
+        KeyCode::Home => "Home".into(),
+        KeyCode::End => "End".into(),
+        KeyCode::PageUp => "PageUp".into(),
+        KeyCode::PageDown => "PageDown".into(),
         _ => "?".into(),
     }
 }
@@ -41,3 +57,59 @@ pub fn format_duration(duration: Duration) -> String {
 
     format!("{}d", days)
 }
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "K", "M", "G"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{}B", bytes),
+        _ if value < 10.0 => format!("{:.1}{}", value, UNITS[unit]),
+        _ => format!("{:.0}{}", value, UNITS[unit]),
+    }
+}
+
+pub fn format_exit(status: &ExitStatus) -> String {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => format!("exit {}", code),
+        (None, Some(signal)) => format!("signal {}", signal),
+        _ => "unknown exit".into(),
+    }
+}
+
+/// Time of day for today, otherwise prefixed with the weekday
+pub fn format_next_run(time: DateTime<Local>, now: DateTime<Local>) -> String {
+    if time.date_naive() == now.date_naive() {
+        time.format("%H:%M").to_string()
+    } else {
+        time.format("%a %H:%M").to_string()
+    }
+}
+
+/// FNV-1a, used because its output is stable across Rust versions unlike
+/// `DefaultHasher`, which matters for hashes kept on disk or compared
+/// between runs
+pub struct Fnv(u64);
+
+impl Fnv {
+    pub fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
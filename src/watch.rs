@@ -0,0 +1,107 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Watches files matching a process's `watch` globs and remembers the most
+/// recent change
+pub struct FileWatcher {
+    _watcher: RecommendedWatcher,
+    changed: Arc<Mutex<Option<(Instant, String)>>>,
+}
+
+impl FileWatcher {
+    pub fn new(cwd: Option<&str>, watch: &[String], ignore: &[String]) -> Result<Self, String> {
+        let base = Path::new(cwd.unwrap_or("."))
+            .canonicalize()
+            .map_err(|e| format!("Cannot watch {}: {e}", cwd.unwrap_or(".")))?;
+
+        let watch_set = glob_set(watch)?;
+        let ignore_set = glob_set(ignore)?;
+
+        let changed = Arc::new(Mutex::new(None));
+        let last_change = changed.clone();
+        let root = base.clone();
+
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            let Ok(event) = res else {
+                return;
+            };
+
+            if event.kind.is_access() {
+                return;
+            }
+
+            for path in event.paths {
+                let Ok(relative) = path.strip_prefix(&root) else {
+                    continue;
+                };
+
+                if watch_set.is_match(relative) && !ignore_set.is_match(relative) {
+                    let display = relative.to_string_lossy().to_string();
+                    *last_change.lock().unwrap() = Some((Instant::now(), display));
+                }
+            }
+        })
+        .map_err(|e| format!("Cannot watch files: {e}"))?;
+
+        for pattern in watch {
+            let (root, mode) = watch_root(pattern);
+            let root = base.join(root);
+
+            watcher
+                .watch(&root, mode)
+                .map_err(|e| format!("Cannot watch {}: {e}", root.display()))?;
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            changed,
+        })
+    }
+
+    /// Returns the last changed path once no further change came in for
+    /// `debounce`
+    pub fn take_change(&self, debounce: Duration) -> Option<String> {
+        let mut changed = self.changed.lock().unwrap();
+
+        match *changed {
+            Some((at, _)) if at.elapsed() >= debounce => changed.take().map(|x| x.1),
+            _ => None,
+        }
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|e| format!("Invalid glob {pattern}: {e}"))?;
+        builder.add(glob);
+    }
+
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// What actually gets watched for a glob: the directory part before any
+/// wildcards (e.g. `src` for `src/**/*.rs`), or the parent directory of a
+/// plain path
+fn watch_root(pattern: &str) -> (PathBuf, RecursiveMode) {
+    let mut prefix = PathBuf::new();
+
+    for component in Path::new(pattern).components() {
+        if let Component::Normal(part) = component
+            && part.to_string_lossy().contains(['*', '?', '[', '{'])
+        {
+            return (prefix, RecursiveMode::Recursive);
+        }
+        prefix.push(component);
+    }
+
+    prefix.pop();
+    (prefix, RecursiveMode::NonRecursive)
+}